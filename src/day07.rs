@@ -1,17 +1,31 @@
 use aoc_runner_derive::aoc;
 use aoc_runner_derive::aoc_generator;
+use slab_tree::NodeId;
 use slab_tree::NodeRef;
+use slab_tree::RemoveBehavior;
 use slab_tree::Tree;
 use slab_tree::TreeBuilder;
+use std::cell::OnceCell;
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
 
 #[aoc_generator(day7)]
 pub fn parse_input(input: &str) -> Vec<String> {
     input.lines().map(|s| s.to_string()).collect()
 }
 
-struct DirEntry {
-    name: String,
-    file_size: u64,
+pub struct DirEntry {
+    pub name: String,
+    pub file_size: u64,
+    dir: bool,
 }
 
 impl DirEntry {
@@ -19,6 +33,7 @@ impl DirEntry {
         DirEntry {
             name: name.to_owned(),
             file_size: 0,
+            dir: true,
         }
     }
 
@@ -26,131 +41,632 @@ impl DirEntry {
         DirEntry {
             name: name.to_owned(),
             file_size: size,
+            dir: false,
         }
     }
 
-    fn is_dir(&self) -> bool {
-        self.file_size == 0
+    pub fn is_dir(&self) -> bool {
+        self.dir
     }
 }
 
-fn parse_commands(input: &[String]) -> Tree<DirEntry> {
-    let mut root = TreeBuilder::new()
-        .with_root(DirEntry::directory("/"))
-        .build();
-    let mut cwd = root.root().unwrap().node_id();
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FsError {
+    NotFound(String),
+    NotADirectory(String),
+    IsADirectory(String),
+    AlreadyExists(String),
+    InvalidName(String),
+    RemoveRoot,
+    UnknownCommand(String),
+    MissingArgument(String),
+    /// Output line that isn't part of an `ls` listing or can't be parsed.
+    UnexpectedOutput(String),
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FsError::NotFound(path) => write!(f, "No such file or directory: {}", path),
+            FsError::NotADirectory(path) => write!(f, "Not a directory: {}", path),
+            FsError::IsADirectory(path) => write!(f, "Is a directory: {}", path),
+            FsError::AlreadyExists(path) => write!(f, "File exists: {}", path),
+            FsError::InvalidName(name) => write!(f, "Invalid name '{}'", name),
+            FsError::RemoveRoot => write!(f, "Can't remove the root directory"),
+            FsError::UnknownCommand(command) => write!(f, "Unknown command '{}'", command),
+            FsError::MissingArgument(command) => write!(f, "Missing argument for '{}'", command),
+            FsError::UnexpectedOutput(line) => write!(f, "Unexpected output '{}'", line),
+        }
+    }
+}
+
+/// Error found while replaying a terminal transcript, with the line (counting
+/// from 1) where it happened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptError {
+    pub line: usize,
+    pub error: FsError,
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}", self.error, self.line)
+    }
+}
+
+fn check_name(name: &str) -> Result<(), FsError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(FsError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Filesystem rebuilt from the commands typed in the device terminal.
+pub struct FileSystem {
+    tree: Tree<DirEntry>,
+    cwd: NodeId,
+    // true while reading the output of `ls`
+    listing: bool,
+    // total size of every node, computed when first needed and dropped on
+    // any change
+    sizes: OnceCell<HashMap<NodeId, u64>>,
+    // children of every directory by name, to avoid scanning big listings
+    names: HashMap<NodeId, HashMap<String, NodeId>>,
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        FileSystem::new()
+    }
+}
+
+impl FileSystem {
+    /// An empty filesystem with the working directory at the root.
+    pub fn new() -> FileSystem {
+        let tree = TreeBuilder::new()
+            .with_root(DirEntry::directory("/"))
+            .build();
+        let cwd = tree.root_id().unwrap();
+        FileSystem {
+            tree,
+            cwd,
+            listing: false,
+            sizes: OnceCell::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn from_transcript(input: &[String]) -> Result<FileSystem, TranscriptError> {
+        let mut fs = FileSystem::new();
+        for (i, line) in input.iter().enumerate() {
+            fs.execute(line)
+                .map_err(|error| TranscriptError { line: i + 1, error })?;
+        }
+        Ok(fs)
+    }
 
-    for line in input.iter().skip(1) {
-        // assume input starts with cd /
-        if line.starts_with('$') {
-            let parts: Vec<&str> = line.split(' ').collect();
-            let command = parts[1];
-            match command {
-                "cd" => {
-                    // change current working directory
-                    let arg = parts[2];
-                    if arg == ".." {
-                        let parent_id = root.get(cwd).unwrap().parent().unwrap().node_id();
-                        cwd = parent_id;
-                    } else {
-                        let cwd_entry = root.get(cwd).unwrap();
-                        let child = cwd_entry.children().find(|c| c.data().name == arg).unwrap();
-                        cwd = child.node_id();
-                    }
+    pub fn tree(&self) -> &Tree<DirEntry> {
+        &self.tree
+    }
+
+    pub fn into_tree(self) -> Tree<DirEntry> {
+        self.tree
+    }
+
+    pub fn root(&self) -> NodeRef<'_, DirEntry> {
+        self.tree.root().unwrap()
+    }
+
+    /// Absolute path of a node.
+    pub fn path(&self, node_id: NodeId) -> String {
+        let node = self.tree.get(node_id).unwrap();
+        if node.parent().is_none() {
+            return "/".to_string();
+        }
+        let mut names = node
+            .ancestors()
+            .map(|n| n.data().name.as_str())
+            .collect::<Vec<&str>>();
+        names.pop(); // root
+        names.reverse();
+        names.push(&node.data().name);
+        format!("/{}", names.join("/"))
+    }
+
+    pub fn pwd(&self) -> String {
+        self.path(self.cwd)
+    }
+
+    fn child(&self, node_id: NodeId, name: &str) -> Option<NodeId> {
+        self.names.get(&node_id)?.get(name).copied()
+    }
+
+    fn append(&mut self, parent: NodeId, entry: DirEntry) -> NodeId {
+        self.sizes.take();
+        let name = entry.name.clone();
+        let mut node = self.tree.get_mut(parent).unwrap();
+        let id = node.append(entry).node_id();
+        self.names.entry(parent).or_default().insert(name, id);
+        id
+    }
+
+    fn is_dir(&self, node_id: NodeId) -> bool {
+        self.tree.get(node_id).unwrap().data().is_dir()
+    }
+
+    // follows a path from the working directory (or the root if absolute),
+    // creating the missing directories if `create` is set
+    fn walk(&mut self, path: &str, create: bool) -> Result<NodeId, FsError> {
+        let mut current = if path.starts_with('/') {
+            self.tree.root_id().unwrap()
+        } else {
+            self.cwd
+        };
+        for name in path.split('/').filter(|n| !n.is_empty() && *n != ".") {
+            if !self.is_dir(current) {
+                return Err(FsError::NotADirectory(self.path(current)));
+            }
+            if name == ".." {
+                // like in a real shell, the parent of the root is the root
+                if let Some(parent) = self.tree.get(current).unwrap().parent() {
+                    current = parent.node_id();
                 }
-                "ls" => {}
-                _ => panic!("Unhandled command"),
+                continue;
             }
-        } else if line.starts_with("dir") {
-            let name = line.split(' ').nth(1).unwrap();
+            current = match self.child(current, name) {
+                Some(child) => child,
+                None if create => {
+                    check_name(name)?;
+                    self.append(current, DirEntry::directory(name))
+                }
+                None => {
+                    let parent = self.path(current);
+                    return Err(FsError::NotFound(
+                        format!("{}/{}", parent, name).replace("//", "/"),
+                    ));
+                }
+            };
+        }
+        Ok(current)
+    }
 
-            let mut node = root.get_mut(cwd).unwrap();
-            node.append(DirEntry::directory(name));
+    // directory holding the last component of `path`, and its name
+    fn walk_parent<'a>(&mut self, path: &'a str) -> Result<(NodeId, &'a str), FsError> {
+        let (parent, name) = match path.trim_end_matches('/').rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((parent, name)) => (parent, name),
+            None => (".", path),
+        };
+        check_name(name)?;
+        let parent = self.walk(parent, false)?;
+        if !self.is_dir(parent) {
+            return Err(FsError::NotADirectory(self.path(parent)));
+        }
+        Ok((parent, name))
+    }
+
+    /// Changes the working directory. Since the transcript comes from a real
+    /// session, directories not listed yet are assumed to exist.
+    pub fn cd(&mut self, path: &str) -> Result<(), FsError> {
+        let target = self.walk(path, true)?;
+        if !self.is_dir(target) {
+            return Err(FsError::NotADirectory(self.path(target)));
+        }
+        self.cwd = target;
+        Ok(())
+    }
+
+    pub fn mkdir(&mut self, path: &str) -> Result<(), FsError> {
+        let (parent, name) = self.walk_parent(path)?;
+        if let Some(existing) = self.child(parent, name) {
+            return Err(FsError::AlreadyExists(self.path(existing)));
+        }
+        self.append(parent, DirEntry::directory(name));
+        Ok(())
+    }
+
+    /// Creates an empty file, leaving existing entries untouched.
+    pub fn touch(&mut self, path: &str) -> Result<(), FsError> {
+        let (parent, name) = self.walk_parent(path)?;
+        if self.child(parent, name).is_none() {
+            self.append(parent, DirEntry::file(name, 0));
+        }
+        Ok(())
+    }
+
+    /// Removes a file or a whole directory. If the working directory was
+    /// inside, it moves to the parent of the removed entry.
+    pub fn rm(&mut self, path: &str) -> Result<(), FsError> {
+        let target = self.walk(path, false)?;
+        let node = self.tree.get(target).unwrap();
+        let Some(parent) = node.parent().map(|p| p.node_id()) else {
+            return Err(FsError::RemoveRoot);
+        };
+        let cwd = self.tree.get(self.cwd).unwrap();
+        if self.cwd == target || cwd.ancestors().any(|a| a.node_id() == target) {
+            self.cwd = parent;
+        }
+        for removed in node.traverse_pre_order().filter(|n| n.data().is_dir()) {
+            self.names.remove(&removed.node_id());
+        }
+        let name = &node.data().name;
+        self.names.get_mut(&parent).unwrap().remove(name);
+        self.tree.remove(target, RemoveBehavior::DropChildren);
+        self.sizes.take();
+        Ok(())
+    }
+
+    // adds an entry from the output of `ls` to the working directory. Listing
+    // a directory again updates the entries instead of duplicating them.
+    fn add_listed(&mut self, line: &str) -> Result<(), FsError> {
+        let unexpected = || FsError::UnexpectedOutput(line.to_string());
+        let (info, name) = line.split_once(' ').ok_or_else(unexpected)?;
+        check_name(name)?;
+        let existing = self.child(self.cwd, name);
+        if info == "dir" {
+            match existing {
+                Some(id) if !self.is_dir(id) => Err(FsError::NotADirectory(self.path(id))),
+                Some(_) => Ok(()),
+                None => {
+                    self.append(self.cwd, DirEntry::directory(name));
+                    Ok(())
+                }
+            }
         } else {
-            // file
-            let split = line.split(' ').collect::<Vec<&str>>();
-            let file_size = split[0].parse::<u64>().unwrap();
-            let file_name = split[1];
+            let size = info.parse::<u64>().map_err(|_| unexpected())?;
+            match existing {
+                Some(id) if self.is_dir(id) => Err(FsError::IsADirectory(self.path(id))),
+                Some(id) => {
+                    self.tree.get_mut(id).unwrap().data().file_size = size;
+                    self.sizes.take();
+                    Ok(())
+                }
+                None => {
+                    self.append(self.cwd, DirEntry::file(name, size));
+                    Ok(())
+                }
+            }
+        }
+    }
 
-            let mut node = root.get_mut(cwd).unwrap();
-            node.append(DirEntry::file(file_name, file_size));
+    /// Replays a line of the terminal transcript: either a `$` command
+    /// (`cd`, `ls`, `mkdir`, `touch` or `rm`) or a line of `ls` output.
+    pub fn execute(&mut self, line: &str) -> Result<(), FsError> {
+        let Some(command) = line.strip_prefix("$ ") else {
+            if !self.listing {
+                return Err(FsError::UnexpectedOutput(line.to_string()));
+            }
+            return self.add_listed(line);
+        };
+        self.listing = false;
+        let parts = command.split_whitespace().collect::<Vec<&str>>();
+        let Some(name) = parts.first() else {
+            return Err(FsError::UnknownCommand(String::new()));
+        };
+        let arg = || {
+            parts
+                .get(1)
+                .copied()
+                .ok_or_else(|| FsError::MissingArgument(name.to_string()))
+        };
+        match *name {
+            "cd" => self.cd(arg()?),
+            "ls" => {
+                self.listing = true;
+                Ok(())
+            }
+            "mkdir" => self.mkdir(arg()?),
+            "touch" => self.touch(arg()?),
+            "rm" => self.rm(arg()?),
+            _ => Err(FsError::UnknownCommand(name.to_string())),
         }
     }
-    root
 }
 
-fn sum_file_size(tree: &NodeRef<DirEntry>) -> u64 {
-    tree.traverse_pre_order()
-        .fold(0, |accum, node| accum + node.data().file_size)
+pub fn parse_commands(input: &[String]) -> Result<Tree<DirEntry>, TranscriptError> {
+    FileSystem::from_transcript(input).map(FileSystem::into_tree)
 }
 
-fn sum_size_with_limit(tree: NodeRef<DirEntry>, limit: Option<u64>) -> u64 {
-    let size_limit = match limit {
-        Some(n) => n,
-        None => u64::MAX,
-    };
-    tree.traverse_level_order().fold(0, |accum, node| {
-        if node.data().is_dir() {
-            let size = sum_file_size(&node);
-            if size < size_limit {
-                return accum + size;
+/// Size with at most 3 significant digits and a binary unit suffix, rounding
+/// up like `du -h` does.
+pub fn human_size(size: u64) -> String {
+    let units = ["K", "M", "G", "T", "P"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    // rounding up to 1024 moves to the next unit, so 1023.5K shows as 1.0M
+    while value.ceil() >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 && (value * 10.0).ceil() < 100.0 {
+        format!("{:.1}{}", (value * 10.0).ceil() / 10.0, units[unit])
+    } else {
+        format!("{}{}", value.ceil(), units[unit])
+    }
+}
+
+impl FileSystem {
+    /// Total size of a file, or of everything inside a directory.
+    pub fn size(&self, node_id: NodeId) -> u64 {
+        self.sizes()[&node_id]
+    }
+
+    // sizes of every node in a single post-order pass, where the children
+    // are always done before their parent
+    fn sizes(&self) -> &HashMap<NodeId, u64> {
+        self.sizes.get_or_init(|| {
+            let mut sizes = HashMap::new();
+            for node in self.root().traverse_post_order() {
+                let children = node.children().map(|c| sizes[&c.node_id()]).sum::<u64>();
+                sizes.insert(node.node_id(), node.data().file_size + children);
             }
+            sizes
+        })
+    }
+
+    // every directory in pre-order with its depth and total size
+    fn directories(&self) -> Vec<(NodeId, usize, u64)> {
+        let sizes = self.sizes();
+        let mut depths = HashMap::new();
+        self.root()
+            .traverse_pre_order()
+            .filter(|node| node.data().is_dir())
+            .map(|node| {
+                let depth = node.parent().map_or(0, |p| depths[&p.node_id()] + 1);
+                depths.insert(node.node_id(), depth);
+                (node.node_id(), depth, sizes[&node.node_id()])
+            })
+            .collect()
+    }
+
+    /// Directories with a total size below `limit`, as in part 1.
+    pub fn directories_smaller_than(&self, limit: u64) -> Vec<(String, u64)> {
+        self.directories()
+            .into_iter()
+            .filter(|(_, _, size)| *size < limit)
+            .map(|(id, _, size)| (self.path(id), size))
+            .collect()
+    }
+
+    /// Smallest directory (other than the root) to delete so that the disk
+    /// has at least `needed` free space, as in part 2.
+    pub fn directory_to_delete(&self, disk_size: u64, needed: u64) -> Option<(String, u64)> {
+        let free = disk_size.saturating_sub(self.size(self.tree.root_id().unwrap()));
+        let missing = needed.saturating_sub(free);
+        self.directories()
+            .into_iter()
+            .skip(1)
+            .filter(|(_, _, size)| *size >= missing)
+            .min_by_key(|(_, _, size)| *size)
+            .map(|(id, _, size)| (self.path(id), size))
+    }
+
+    fn largest(&self, n: usize, dirs: bool) -> Vec<(String, u64)> {
+        let sizes = self.sizes();
+        let mut candidates = self
+            .root()
+            .traverse_pre_order()
+            .filter(|node| node.data().is_dir() == dirs)
+            .map(|node| (node.node_id(), sizes[&node.node_id()]))
+            .collect::<Vec<(NodeId, u64)>>();
+        candidates.sort_by_key(|(_, size)| cmp::Reverse(*size));
+        // only build the paths of the entries that can make the cut, ties
+        // included since those are sorted by path
+        let threshold = n.checked_sub(1).and_then(|i| candidates.get(i));
+        if let Some((_, threshold)) = threshold.copied() {
+            candidates.retain(|(_, size)| *size >= threshold);
         }
-        accum
-    })
-}
+        let mut entries = self.paths(candidates);
+        entries.truncate(n);
+        entries
+    }
 
-#[allow(dead_code)]
-fn println(tree: &NodeRef<DirEntry>) {
-    let mut output = String::new();
-    tree.traverse_pre_order().for_each(|node| {
-        let depth = node.ancestors().count();
-        let indent = "  ".repeat(depth);
-        let entry = node.data();
-        let info = if entry.is_dir() {
-            "(dir)".to_string()
-        } else {
-            format!("(file, size={})", entry.file_size)
-        };
-        let line = format!("{}- {} {}\n", indent, entry.name, info);
-        output.push_str(&line);
-    });
-    println!("{}", output);
+    fn paths(&self, entries: Vec<(NodeId, u64)>) -> Vec<(String, u64)> {
+        let mut entries = entries
+            .into_iter()
+            .map(|(id, size)| (self.path(id), size))
+            .collect::<Vec<(String, u64)>>();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        entries
+    }
+
+    /// The `n` biggest directories by total size, biggest first.
+    pub fn largest_directories(&self, n: usize) -> Vec<(String, u64)> {
+        self.largest(n, true)
+    }
+
+    pub fn largest_files(&self, n: usize) -> Vec<(String, u64)> {
+        self.largest(n, false)
+    }
+
+    /// Directory sizes like `du --max-depth`, children before their parent.
+    /// Sizes are in bytes unless `human` is set.
+    pub fn du(&self, max_depth: Option<usize>, human: bool) -> String {
+        let mut lines = self
+            .directories()
+            .into_iter()
+            .filter(|(_, depth, _)| max_depth.is_none_or(|max| *depth <= max))
+            .map(|(id, _, size)| {
+                let size = if human {
+                    human_size(size)
+                } else {
+                    size.to_string()
+                };
+                format!("{}\t{}", size, self.path(id))
+            })
+            .collect::<Vec<String>>();
+        // reversed pre-order lists every directory after its contents
+        lines.reverse();
+        lines.join("\n")
+    }
+
+    /// Drawing of the whole filesystem like `tree`, with the total size of
+    /// every entry.
+    pub fn tree_report(&self) -> String {
+        let mut output = String::new();
+        self.tree_lines(&self.root(), "", &mut output);
+        output
+    }
+
+    fn tree_lines(&self, node: &NodeRef<DirEntry>, prefix: &str, output: &mut String) {
+        if node.parent().is_none() {
+            output.push_str(&format!("/ ({})\n", self.size(node.node_id())));
+        }
+        let children = node.children().collect::<Vec<NodeRef<DirEntry>>>();
+        for (i, child) in children.iter().enumerate() {
+            let last = i == children.len() - 1;
+            let entry = child.data();
+            output.push_str(&format!(
+                "{}{} {}{} ({})\n",
+                prefix,
+                if last { "└──" } else { "├──" },
+                entry.name,
+                if entry.is_dir() { "/" } else { "" },
+                self.size(child.node_id())
+            ));
+            if entry.is_dir() {
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                self.tree_lines(child, &prefix, output);
+            }
+        }
+    }
 }
 
-#[aoc(day7, part1)]
-pub fn sum_directories_smaller_than_100k(input: &[String]) -> u64 {
-    let dir = parse_commands(input);
-    sum_size_with_limit(dir.root().unwrap(), Some(100_000))
+fn invalid_name(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Name {:?} can't be used in a transcript", name),
+    )
 }
 
-fn smallest_bigger_than(tree: &NodeRef<DirEntry>, minimum_size: u64) -> Option<u64> {
-    let mut best_size = u64::MAX;
+impl FileSystem {
+    /// Recreates the filesystem under `target`. Files get the recorded size
+    /// but no contents, so they are sparse on filesystems supporting it.
+    pub fn export_to(&self, target: &Path) -> io::Result<()> {
+        fs::create_dir_all(target)?;
+        for node in self.root().traverse_pre_order().skip(1) {
+            let path = target.join(&self.path(node.node_id())[1..]);
+            if node.data().is_dir() {
+                fs::create_dir_all(&path)?;
+            } else {
+                File::create(&path)?.set_len(node.data().file_size)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the filesystem as a tar archive, with files full of zeros.
+    pub fn write_tar<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut builder = tar::Builder::new(writer);
+        for node in self.root().traverse_pre_order().skip(1) {
+            let path = self.path(node.node_id());
+            let entry = node.data();
+            let mut header = tar::Header::new_gnu();
+            header.set_mtime(0);
+            if entry.is_dir() {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, &path[1..], io::empty())?;
+            } else {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(entry.file_size);
+                let contents = io::repeat(0).take(entry.file_size);
+                builder.append_data(&mut header, &path[1..], contents)?;
+            }
+        }
+        builder.into_inner()?.flush()
+    }
+
+    pub fn save_tar(&self, path: &Path) -> io::Result<()> {
+        self.write_tar(BufWriter::new(File::create(path)?))
+    }
 
-    tree.traverse_level_order().skip(1).for_each(|node| {
-        if node.data().is_dir() {
-            let size = sum_file_size(&node);
-            if size >= minimum_size && size < best_size {
-                best_size = size;
+    /// Scans a real directory. Entries are sorted by name, and anything that
+    /// isn't a regular file or a directory (like symlinks) is skipped.
+    pub fn from_directory(root: &Path) -> io::Result<FileSystem> {
+        let mut result = FileSystem::new();
+        let mut pending = vec![(root.to_path_buf(), result.tree.root_id().unwrap())];
+        while let Some((dir, id)) = pending.pop() {
+            let mut entries = fs::read_dir(&dir)?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+            entries.sort_by_key(|e| e.file_name());
+            for entry in entries {
+                let name = entry
+                    .file_name()
+                    .into_string()
+                    .map_err(|name| invalid_name(&name.to_string_lossy()))?;
+                // `cd` takes a single word
+                if name.contains(char::is_whitespace) || check_name(&name).is_err() {
+                    return Err(invalid_name(&name));
+                }
+                let metadata = entry.metadata()?;
+                if metadata.is_dir() {
+                    let child = result.append(id, DirEntry::directory(&name));
+                    pending.push((entry.path(), child));
+                } else if metadata.is_file() {
+                    result.append(id, DirEntry::file(&name, metadata.len()));
+                }
             }
         }
-    });
+        Ok(result)
+    }
 
-    if best_size != u64::MAX {
-        return Some(best_size);
+    /// Terminal session exploring the whole filesystem like the puzzle
+    /// input: an `ls` of every directory, visited depth first.
+    pub fn transcript(&self) -> Vec<String> {
+        let mut lines = vec!["$ cd /".to_string()];
+        // directories left to visit, where `None` means going back up
+        let mut pending = vec![Some(self.tree.root_id().unwrap())];
+        while let Some(next) = pending.pop() {
+            let Some(id) = next else {
+                lines.push("$ cd ..".to_string());
+                continue;
+            };
+            let node = self.tree.get(id).unwrap();
+            if node.parent().is_some() {
+                lines.push(format!("$ cd {}", node.data().name));
+            }
+            lines.push("$ ls".to_string());
+            let children = node.children().collect::<Vec<NodeRef<DirEntry>>>();
+            for child in children.iter() {
+                let entry = child.data();
+                if entry.is_dir() {
+                    lines.push(format!("dir {}", entry.name));
+                } else {
+                    lines.push(format!("{} {}", entry.file_size, entry.name));
+                }
+            }
+            for child in children.iter().rev().filter(|c| c.data().is_dir()) {
+                pending.push(None);
+                pending.push(Some(child.node_id()));
+            }
+        }
+        // no need to climb back at the end
+        while lines.last().is_some_and(|l| l == "$ cd ..") {
+            lines.pop();
+        }
+        lines
     }
+}
 
-    None
+#[aoc(day7, part1)]
+pub fn sum_directories_smaller_than_100k(input: &[String]) -> u64 {
+    let fs = FileSystem::from_transcript(input).unwrap();
+    fs.directories_smaller_than(100_000)
+        .iter()
+        .map(|(_, size)| size)
+        .sum()
 }
 
 #[aoc(day7, part2)]
 pub fn find_directory_free_30gb(input: &[String]) -> u64 {
-    let dir = parse_commands(input);
-    let total = sum_file_size(&dir.root().unwrap());
-    let free = 70_000_000 - total;
-    let missing = 30_000_000 - free;
-
-    smallest_bigger_than(&dir.root().unwrap(), missing).unwrap()
+    let fs = FileSystem::from_transcript(input).unwrap();
+    let (_, size) = fs.directory_to_delete(70_000_000, 30_000_000).unwrap();
+    size
 }
 
 #[cfg(test)]
@@ -192,4 +708,301 @@ $ ls
         let input = parse_input(DAY07_EXAMPLE);
         assert_eq!(find_directory_free_30gb(&input), 24_933_642);
     }
+
+    fn transcript(lines: &str) -> Vec<String> {
+        parse_input(lines)
+    }
+
+    #[test]
+    fn test_day7_filesystem() {
+        let mut fs = FileSystem::from_transcript(&parse_input(DAY07_EXAMPLE)).unwrap();
+        assert_eq!(fs.pwd(), "/d");
+        assert_eq!(fs.root().traverse_pre_order().count(), 14);
+
+        fs.cd("/a/e").unwrap();
+        assert_eq!(fs.pwd(), "/a/e");
+        fs.cd("../../d/.").unwrap();
+        assert_eq!(fs.pwd(), "/d");
+        fs.cd("..").unwrap();
+        fs.cd("..").unwrap();
+        assert_eq!(fs.pwd(), "/");
+
+        // directories can be entered before listing them
+        fs.cd("x/y").unwrap();
+        assert_eq!(fs.pwd(), "/x/y");
+
+        fs.mkdir("/x/z").unwrap();
+        fs.touch("../z/empty").unwrap();
+        fs.touch("/b.txt").unwrap();
+        let total = |fs: &FileSystem| {
+            fs.root()
+                .traverse_pre_order()
+                .map(|n| n.data().file_size)
+                .sum::<u64>()
+        };
+        assert_eq!(total(&fs), 48_381_165);
+        assert_eq!(fs.root().traverse_pre_order().count(), 18);
+
+        fs.rm("/x").unwrap();
+        assert_eq!(fs.pwd(), "/");
+        fs.rm("a/e/i").unwrap();
+        assert_eq!(total(&fs), 48_381_165 - 584);
+        assert_eq!(fs.root().traverse_pre_order().count(), 13);
+    }
+
+    #[test]
+    fn test_day7_repeated_ls() {
+        let input = transcript(
+            "$ cd /a
+$ ls
+dir b
+10 c
+$ cd /
+$ ls
+dir a
+$ cd a
+$ ls
+dir b
+12 c",
+        );
+        let fs = FileSystem::from_transcript(&input).unwrap();
+        let sizes = fs
+            .root()
+            .traverse_pre_order()
+            .map(|n| (n.data().name.clone(), n.data().file_size))
+            .collect::<Vec<(String, u64)>>();
+        let expected = [("/", 0), ("a", 0), ("b", 0), ("c", 12)];
+        assert_eq!(
+            sizes,
+            expected
+                .iter()
+                .map(|(n, s)| (n.to_string(), *s))
+                .collect::<Vec<(String, u64)>>()
+        );
+    }
+
+    #[test]
+    fn test_day7_errors() {
+        let error = |lines: &str| FileSystem::from_transcript(&transcript(lines)).err();
+        assert_eq!(
+            error("$ ls\n10 a\n$ cd a"),
+            Some(TranscriptError {
+                line: 3,
+                error: FsError::NotADirectory("/a".to_string())
+            })
+        );
+        assert_eq!(
+            error("$ ls\ndir a\n$ cd a\n$ ls\n1 x\n$ cd /\n$ ls\n5 a")
+                .unwrap()
+                .to_string(),
+            "Is a directory: /a at line 8"
+        );
+        assert_eq!(
+            error("$ cd /\ndir a").unwrap().error,
+            FsError::UnexpectedOutput("dir a".to_string())
+        );
+        assert_eq!(
+            error("$ ls\nbig a").unwrap().error,
+            FsError::UnexpectedOutput("big a".to_string())
+        );
+        assert_eq!(
+            error("$ pwd").unwrap().error,
+            FsError::UnknownCommand("pwd".to_string())
+        );
+        assert_eq!(
+            error("$ cd").unwrap().error,
+            FsError::MissingArgument("cd".to_string())
+        );
+        assert_eq!(
+            error("$ mkdir a\n$ mkdir /a").unwrap().error,
+            FsError::AlreadyExists("/a".to_string())
+        );
+        assert_eq!(
+            error("$ mkdir a/b").unwrap().error,
+            FsError::NotFound("/a".to_string())
+        );
+        assert_eq!(
+            error("$ touch a\n$ mkdir a/b").unwrap().error,
+            FsError::NotADirectory("/a".to_string())
+        );
+        assert_eq!(error("$ rm /").unwrap().error, FsError::RemoveRoot);
+        assert_eq!(error("$ rm ..").unwrap().error, FsError::RemoveRoot);
+        assert_eq!(
+            error("$ touch ..").unwrap().error,
+            FsError::InvalidName("..".to_string())
+        );
+    }
+
+    #[test]
+    fn test_day7_queries() {
+        let fs = FileSystem::from_transcript(&parse_input(DAY07_EXAMPLE)).unwrap();
+        assert_eq!(
+            fs.directories_smaller_than(100_000),
+            vec![("/a".to_string(), 94_853), ("/a/e".to_string(), 584)]
+        );
+        assert_eq!(
+            fs.directory_to_delete(70_000_000, 30_000_000),
+            Some(("/d".to_string(), 24_933_642))
+        );
+        assert_eq!(fs.directory_to_delete(70_000_000, 100_000_000), None);
+        assert_eq!(
+            fs.largest_directories(2),
+            vec![
+                ("/".to_string(), 48_381_165),
+                ("/d".to_string(), 24_933_642)
+            ]
+        );
+        assert_eq!(
+            fs.largest_files(2),
+            vec![
+                ("/b.txt".to_string(), 14_848_514),
+                ("/c.dat".to_string(), 8_504_156)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_day7_reports() {
+        let fs = FileSystem::from_transcript(&parse_input(DAY07_EXAMPLE)).unwrap();
+        assert_eq!(
+            fs.du(None, false),
+            "24933642\t/d\n584\t/a/e\n94853\t/a\n48381165\t/"
+        );
+        assert_eq!(fs.du(Some(1), true), "24M\t/d\n93K\t/a\n47M\t/");
+        assert_eq!(fs.du(Some(0), false), "48381165\t/");
+
+        assert_eq!(
+            fs.tree_report(),
+            "/ (48381165)
+├── a/ (94853)
+│   ├── e/ (584)
+│   │   └── i (584)
+│   ├── f (29116)
+│   ├── g (2557)
+│   └── h.lst (62596)
+├── b.txt (14848514)
+├── c.dat (8504156)
+└── d/ (24933642)
+    ├── j (4060174)
+    ├── d.log (8033020)
+    ├── d.ext (5626152)
+    └── k (7214296)
+"
+        );
+    }
+
+    #[test]
+    fn test_day7_human_size() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(10 * 1024 - 1), "10K");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0M");
+        assert_eq!(human_size(1024 * 1024), "1.0M");
+        assert_eq!(human_size(1024 * 1024 + 1), "1.1M");
+        assert_eq!(human_size(1023 * 1024), "1023K");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0G");
+    }
+
+    #[test]
+    fn test_day7_cached_sizes() {
+        let mut fs = FileSystem::from_transcript(&parse_input(DAY07_EXAMPLE)).unwrap();
+        let root = fs.tree().root_id().unwrap();
+        assert_eq!(fs.size(root), 48_381_165);
+        fs.execute("$ ls").unwrap();
+        fs.execute("1000 j").unwrap();
+        fs.execute("15 new").unwrap();
+        assert_eq!(fs.size(root), 48_381_165 - 4_060_174 + 1015);
+        fs.rm("/d").unwrap();
+        assert_eq!(fs.size(root), 23_447_523);
+        assert_eq!(fs.largest_directories(0), vec![]);
+        assert_eq!(fs.largest_files(10).len(), 6);
+    }
+
+    #[test]
+    fn test_day7_big_transcript() {
+        // a deep chain of directories holding lots of files
+        let mut lines = Vec::new();
+        for depth in 0..500 {
+            lines.push("$ ls".to_string());
+            lines.push(format!("dir d{}", depth));
+            for file in 0..100 {
+                lines.push(format!("{} f{}", file + 1, file));
+            }
+            lines.push(format!("$ cd d{}", depth));
+        }
+        let fs = FileSystem::from_transcript(&lines).unwrap();
+        assert_eq!(
+            fs.largest_directories(1),
+            vec![("/".to_string(), 500 * 5050)]
+        );
+        assert_eq!(fs.directories_smaller_than(5051).len(), 2);
+        let (path, size) = fs.directory_to_delete(70_000_000, 69_000_000).unwrap();
+        assert!(path.ends_with("/d196/d197"));
+        assert_eq!(size, 302 * 5050);
+    }
+
+    const SMALL_EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+dir c
+2000 d
+$ cd c
+$ ls
+0 e";
+
+    #[test]
+    fn test_day7_transcript() {
+        let input = parse_input(DAY07_EXAMPLE);
+        let fs = FileSystem::from_transcript(&input).unwrap();
+        assert_eq!(fs.transcript(), input);
+    }
+
+    #[test]
+    fn test_day7_tar() {
+        let fs = FileSystem::from_transcript(&parse_input(SMALL_EXAMPLE)).unwrap();
+        let mut buffer = Vec::new();
+        fs.write_tar(&mut buffer).unwrap();
+        let mut archive = tar::Archive::new(buffer.as_slice());
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                let path = e.path().unwrap().to_string_lossy().to_string();
+                (path, e.header().entry_type().is_dir(), e.size())
+            })
+            .collect::<Vec<(String, bool, u64)>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("a".to_string(), true, 0),
+                ("a/c".to_string(), true, 0),
+                ("a/c/e".to_string(), false, 0),
+                ("a/d".to_string(), false, 2000),
+                ("b".to_string(), false, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_day7_export() {
+        let fs = FileSystem::from_transcript(&parse_input(SMALL_EXAMPLE)).unwrap();
+        let target = std::env::temp_dir().join(format!("aoc-day7-{}", std::process::id()));
+        fs.export_to(&target).unwrap();
+        assert_eq!(std::fs::metadata(target.join("a/d")).unwrap().len(), 2000);
+        assert!(std::fs::metadata(target.join("a/c")).unwrap().is_dir());
+
+        let scanned = FileSystem::from_directory(&target).unwrap();
+        assert_eq!(scanned.transcript(), parse_input(SMALL_EXAMPLE));
+
+        File::create(target.join("a/with space")).unwrap();
+        let error = FileSystem::from_directory(&target).err().unwrap();
+        std::fs::remove_dir_all(&target).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
 }
@@ -1,92 +1,739 @@
 use aoc_runner_derive::aoc;
 use aoc_runner_derive::aoc_generator;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Register {
+    X,
+    Y,
+    Z,
+    W,
+}
+
+impl Register {
+    fn index(&self) -> usize {
+        match self {
+            Register::X => 0,
+            Register::Y => 1,
+            Register::Z => 2,
+            Register::W => 3,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x" => Ok(Register::X),
+            "y" => Ok(Register::Y),
+            "z" => Ok(Register::Z),
+            "w" => Ok(Register::W),
+            _ => Err(format!("Unknown register '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Register::X => "x",
+            Register::Y => "y",
+            Register::Z => "z",
+            Register::W => "w",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Value(i64),
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(n) = s.parse::<i64>() {
+            return Ok(Operand::Value(n));
+        }
+        s.parse::<Register>()
+            .map(Operand::Register)
+            .map_err(|_| format!("Expected a number or a register, found '{}'", s))
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "{}", r),
+            Operand::Value(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// Jump targets are absolute instruction indexes, the assembler takes care
+/// of resolving labels and relative offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Instruction {
     Noop,
-    Add(i64),
+    Add(Register, Operand),
+    Set(Register, Operand),
+    Jump(usize),
+    JumpIfZero(Register, usize),
+    JumpIfNotZero(Register, usize),
 }
 
-#[aoc_generator(day10)]
-pub fn parse_input(input: &str) -> Vec<Instruction> {
-    input
-        .lines()
-        .map(|s| {
-            let parts: Vec<&str> = s.split(' ').collect();
-            match parts[0] {
-                "noop" => Instruction::Noop,
-                "addx" => Instruction::Add(parts[1].parse::<i64>().unwrap()),
-                _ => panic!("Wrong input"),
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Noop,
+    Add,
+    Set,
+    Jump,
+    JumpIfZero,
+    JumpIfNotZero,
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::Noop => Opcode::Noop,
+            Instruction::Add(_, _) => Opcode::Add,
+            Instruction::Set(_, _) => Opcode::Set,
+            Instruction::Jump(_) => Opcode::Jump,
+            Instruction::JumpIfZero(_, _) => Opcode::JumpIfZero,
+            Instruction::JumpIfNotZero(_, _) => Opcode::JumpIfNotZero,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Add(r, o) => write!(f, "add{} {}", r, o),
+            Instruction::Set(r, o) => write!(f, "set{} {}", r, o),
+            Instruction::Jump(t) => write!(f, "jmp @{}", t),
+            Instruction::JumpIfZero(r, t) => write!(f, "jz {} @{}", r, t),
+            Instruction::JumpIfNotZero(r, t) => write!(f, "jnz {} @{}", r, t),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// Line of the source where the problem was found, counting from 1.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}", self.reason, self.line)
+    }
+}
+
+// jump target before resolving labels
+enum Target<'a> {
+    Label(&'a str),
+    Offset(i64),
+}
+
+fn parse_target(s: &str) -> Result<Target<'_>, String> {
+    if s.starts_with(['+', '-']) {
+        return s
+            .parse::<i64>()
+            .map(Target::Offset)
+            .map_err(|_| format!("Invalid offset '{}'", s));
+    }
+    if s.is_empty() || !s.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("Invalid label '{}'", s));
+    }
+    Ok(Target::Label(s))
+}
+
+fn expect_args<'a>(args: &[&'a str], count: usize, mnemonic: &str) -> Result<Vec<&'a str>, String> {
+    if args.len() != count {
+        return Err(format!(
+            "'{}' takes {} operands but got {}",
+            mnemonic,
+            count,
+            args.len()
+        ));
+    }
+    Ok(args.to_vec())
+}
+
+/// Assembles a program for the handheld. Besides the puzzle's `noop` and
+/// `addx` it understands:
+///
+/// - `add<r> <v>` and `set<r> <v>` for registers `x`, `y`, `z` and `w`,
+///   where `<v>` is a number or another register.
+/// - `jmp <t>`, `jz <r> <t>` and `jnz <r> <t>`, where the target is a label
+///   or a relative offset like `-2`.
+/// - `label:` definitions, either alone or before an instruction.
+/// - Comments starting with `;` until the end of the line.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut labels = HashMap::new();
+    // instructions with unresolved targets, with their line number
+    let mut pending = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let error = |reason: String| AsmError {
+            line: line_number,
+            reason,
+        };
+        let mut code = line.split(';').next().unwrap().trim();
+        if let Some((label, rest)) = code.split_once(':') {
+            let label = label.trim();
+            if !matches!(parse_target(label), Ok(Target::Label(_))) {
+                return Err(error(format!("Invalid label '{}'", label)));
+            }
+            if labels.insert(label, pending.len()).is_some() {
+                return Err(error(format!("Label '{}' defined twice", label)));
             }
+            code = rest.trim();
+        }
+        if code.is_empty() {
+            continue;
+        }
+
+        let parts = code.split_whitespace().collect::<Vec<&str>>();
+        let (mnemonic, args) = (parts[0], &parts[1..]);
+        let parsed = match mnemonic {
+            "noop" => expect_args(args, 0, mnemonic).map(|_| (Instruction::Noop, None)),
+            "jmp" => expect_args(args, 1, mnemonic)
+                .and_then(|a| parse_target(a[0]))
+                .map(|t| (Instruction::Jump(0), Some(t))),
+            "jz" | "jnz" => expect_args(args, 2, mnemonic).and_then(|a| {
+                let register = a[0].parse::<Register>()?;
+                let target = parse_target(a[1])?;
+                let instruction = if mnemonic == "jz" {
+                    Instruction::JumpIfZero(register, 0)
+                } else {
+                    Instruction::JumpIfNotZero(register, 0)
+                };
+                Ok((instruction, Some(target)))
+            }),
+            _ => match (mnemonic.strip_prefix("add"), mnemonic.strip_prefix("set")) {
+                (Some(register), _) | (_, Some(register)) if register.len() == 1 => {
+                    expect_args(args, 1, mnemonic).and_then(|a| {
+                        let register = register.parse::<Register>()?;
+                        let operand = a[0].parse::<Operand>()?;
+                        if mnemonic.starts_with("add") {
+                            Ok((Instruction::Add(register, operand), None))
+                        } else {
+                            Ok((Instruction::Set(register, operand), None))
+                        }
+                    })
+                }
+                _ => Err(format!("Unknown instruction '{}'", mnemonic)),
+            },
+        };
+        pending.push((line_number, parsed.map_err(error)?));
+    }
+
+    let len = pending.len();
+    pending
+        .iter()
+        .enumerate()
+        .map(|(index, (line, (instruction, target)))| {
+            let Some(target) = target else {
+                return Ok(*instruction);
+            };
+            let address = match target {
+                Target::Label(label) => *labels.get(label).ok_or_else(|| AsmError {
+                    line: *line,
+                    reason: format!("Undefined label '{}'", label),
+                })?,
+                Target::Offset(offset) => {
+                    let address = index as i64 + offset;
+                    // jumping right past the end is a way of halting
+                    if address < 0 || address > len as i64 {
+                        return Err(AsmError {
+                            line: *line,
+                            reason: format!("Offset {} jumps outside of the program", offset),
+                        });
+                    }
+                    address as usize
+                }
+            };
+            Ok(match *instruction {
+                Instruction::JumpIfZero(r, _) => Instruction::JumpIfZero(r, address),
+                Instruction::JumpIfNotZero(r, _) => Instruction::JumpIfNotZero(r, address),
+                _ => Instruction::Jump(address),
+            })
         })
         .collect()
 }
 
-fn cycles_for_instruction(instruction: &Instruction) -> usize {
-    match *instruction {
-        Instruction::Noop => 1,
-        Instruction::Add(_) => 2,
+#[aoc_generator(day10)]
+pub fn parse_input(input: &str) -> Vec<Instruction> {
+    assemble(input).unwrap_or_else(|e| panic!("Wrong input: {}", e))
+}
+
+/// How many cycles each kind of instruction takes.
+#[derive(Clone, Debug)]
+pub struct CycleCosts(HashMap<Opcode, usize>);
+
+impl Default for CycleCosts {
+    /// Puzzle timings for `noop` and `addx`, and one cycle for the rest
+    /// except for conditional jumps, which need an extra one to check the
+    /// register.
+    fn default() -> Self {
+        CycleCosts(HashMap::from([
+            (Opcode::Noop, 1),
+            (Opcode::Add, 2),
+            (Opcode::Set, 1),
+            (Opcode::Jump, 1),
+            (Opcode::JumpIfZero, 2),
+            (Opcode::JumpIfNotZero, 2),
+        ]))
     }
 }
 
-fn get_x_values(instructions: &Vec<Instruction>, interesting: &Vec<usize>) -> Vec<i64> {
-    let mut ret = Vec::new();
-    assert!(interesting.windows(2).all(|w| w[0] < w[1])); // if the cycles are sorted, we can reuse some calculations
-    let mut start_instruction = 0;
-    let mut cycle_counter = 1;
-    let mut x = 1;
-    for cycle in interesting {
-        for i in start_instruction..instructions.len() {
-            let instruction_cycles = cycles_for_instruction(&instructions[i]);
-            if cycle_counter + instruction_cycles > *cycle {
-                x += instructions[start_instruction..i]
-                    .iter()
-                    .fold(0, |x, instruction| match instruction {
-                        Instruction::Add(n) => x + n,
-                        Instruction::Noop => x,
-                    });
+impl CycleCosts {
+    /// Changes the cost of an opcode. Every instruction takes at least a
+    /// cycle.
+    pub fn set(&mut self, opcode: Opcode, cycles: usize) {
+        self.0.insert(opcode, cycles.max(1));
+    }
+
+    pub fn get(&self, opcode: Opcode) -> usize {
+        self.0[&opcode]
+    }
+}
+
+/// Condition that makes `Cpu::run` stop before a cycle starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    Cycle(usize),
+    Register(Register, i64),
+}
+
+/// Why `Cpu::run` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    Breakpoint(Breakpoint),
+}
+
+/// The handheld CPU, stepping one cycle at a time. Registers always hold the
+/// value *during* the cycle about to be executed, which is what the puzzle
+/// asks about.
+pub struct Cpu<'a> {
+    program: &'a [Instruction],
+    registers: [i64; 4],
+    cycle: usize,
+    pc: usize,
+    // cycles already spent on the instruction at `pc`
+    elapsed: usize,
+    costs: CycleCosts,
+    breakpoints: Vec<Breakpoint>,
+    trace: Option<Vec<String>>,
+}
+
+impl<'a> Cpu<'a> {
+    /// X starts at 1 and every other register at 0.
+    pub fn new(program: &'a [Instruction]) -> Cpu<'a> {
+        Cpu {
+            program,
+            registers: [1, 0, 0, 0],
+            cycle: 1,
+            pc: 0,
+            elapsed: 0,
+            costs: CycleCosts::default(),
+            breakpoints: Vec::new(),
+            trace: None,
+        }
+    }
+
+    /// Records a line for every executed cycle, available with `trace`.
+    pub fn with_trace(mut self) -> Cpu<'a> {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    pub fn with_costs(mut self, costs: CycleCosts) -> Cpu<'a> {
+        self.costs = costs;
+        self
+    }
+
+    pub fn x(&self) -> i64 {
+        self.register(Register::X)
+    }
+
+    pub fn register(&self, register: Register) -> i64 {
+        self.registers[register.index()]
+    }
+
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn current_instruction(&self) -> Option<&Instruction> {
+        self.program.get(self.pc)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    pub fn trace(&self) -> &[String] {
+        self.trace.as_deref().unwrap_or(&[])
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|b| *b != breakpoint);
+    }
+
+    fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        self.breakpoints.iter().copied().find(|b| match b {
+            Breakpoint::Cycle(c) => *c == self.cycle,
+            Breakpoint::Register(r, value) => *value == self.register(*r),
+        })
+    }
+
+    fn value(&self, operand: Operand) -> i64 {
+        match operand {
+            Operand::Register(r) => self.register(r),
+            Operand::Value(n) => n,
+        }
+    }
+
+    // applies the effects of an instruction once all its cycles are done
+    fn execute(&mut self, instruction: Instruction) {
+        self.pc += 1;
+        match instruction {
+            Instruction::Noop => (),
+            Instruction::Add(r, o) => self.registers[r.index()] += self.value(o),
+            Instruction::Set(r, o) => self.registers[r.index()] = self.value(o),
+            Instruction::Jump(t) => self.pc = t,
+            Instruction::JumpIfZero(r, t) => {
+                if self.register(r) == 0 {
+                    self.pc = t;
+                }
+            }
+            Instruction::JumpIfNotZero(r, t) => {
+                if self.register(r) != 0 {
+                    self.pc = t;
+                }
+            }
+        }
+    }
 
-                ret.push(x);
-                start_instruction = i;
+    /// Executes a single cycle. Returns false if the program already ended.
+    pub fn step(&mut self) -> bool {
+        let Some(instruction) = self.program.get(self.pc).copied() else {
+            return false;
+        };
+        let cycles = self.costs.get(instruction.opcode());
+        self.elapsed += 1;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(format!(
+                "cycle {:>4}: X={:<4} {} ({}/{})",
+                self.cycle, self.registers[0], instruction, self.elapsed, cycles
+            ));
+        }
+        if self.elapsed >= cycles {
+            self.execute(instruction);
+            self.elapsed = 0;
+        }
+        self.cycle += 1;
+        true
+    }
 
-                break;
+    /// Runs until the program ends or a breakpoint is hit, calling `observer`
+    /// at the start of every cycle. Breakpoints are checked before the
+    /// observer, except on the first cycle so that calling `run` again
+    /// resumes the execution.
+    pub fn run<F: FnMut(&Cpu)>(&mut self, mut observer: F) -> Stop {
+        let mut first = true;
+        while !self.is_halted() {
+            if !first {
+                if let Some(breakpoint) = self.hit_breakpoint() {
+                    return Stop::Breakpoint(breakpoint);
+                }
             }
-            cycle_counter += instruction_cycles;
+            first = false;
+            observer(self);
+            self.step();
         }
+        Stop::Halted
     }
-    ret
 }
 
 #[aoc(day10, part1)]
-fn sum_six_signal_strengths(instructions: &Vec<Instruction>) -> i64 {
-    let interesting = vec![20, 60, 100, 140, 180, 220];
-    let xs = get_x_values(instructions, &interesting);
-    interesting
-        .into_iter()
-        .zip(xs.into_iter())
-        .map(|pair| pair.0 as i64 * pair.1)
-        .sum::<i64>()
+fn sum_six_signal_strengths(instructions: &[Instruction]) -> i64 {
+    let mut cpu = Cpu::new(instructions);
+    cpu.add_breakpoint(Breakpoint::Cycle(221));
+    let mut sum = 0;
+    cpu.run(|cpu| {
+        if cpu.cycle() % 40 == 20 {
+            sum += cpu.cycle() as i64 * cpu.x();
+        }
+    });
+    sum
 }
 
-#[aoc(day10, part2)]
-fn render_crt(instructions: &Vec<Instruction>) -> String {
-    let xs = get_x_values(instructions, &(1..=240).collect::<Vec<usize>>());
-    let mut buffer = "".to_string();
-    for i in 1..=240 {
-        let x = xs[i - 1];
-        let col = ((i - 1) % 40) as i64;
-
-        if (x - col).abs() <= 1 {
-            buffer.push('#');
-        } else {
-            buffer.push('.');
+/// Size of the screen and of the sprite drawn at the X register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrtConfig {
+    pub width: usize,
+    pub height: usize,
+    pub sprite_width: usize,
+}
+
+impl Default for CrtConfig {
+    fn default() -> Self {
+        CrtConfig {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+        }
+    }
+}
+
+/// Pixels drawn by the CRT, row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize) -> Screen {
+        Screen {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Plain PBM, with lit pixels in black.
+    pub fn write_pbm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "P1")?;
+        writeln!(writer, "{} {}", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width) {
+            let bits = row
+                .iter()
+                .map(|lit| if *lit { "1" } else { "0" })
+                .collect::<Vec<&str>>();
+            writeln!(writer, "{}", bits.join(" "))?;
         }
+        Ok(())
+    }
 
-        if col == 39 && i != 240 {
-            //add newlines after column 40, 80
-            buffer.push('\n')
+    /// Grayscale PNG with lit pixels in black, each one drawn as a square of
+    /// `scale` pixels. Fails if `scale` is zero or makes the image too big.
+    pub fn write_png<W: Write>(&self, writer: W, scale: usize) -> Result<(), png::EncodingError> {
+        let scaled = |size: usize| {
+            size.checked_mul(scale)
+                .filter(|&size| size > 0)
+                .and_then(|size| u32::try_from(size).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid scale"))
         };
+        let (width, height) = (scaled(self.width)?, scaled(self.height)?);
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut data = Vec::with_capacity(width as usize * height as usize);
+        for row in self.pixels.chunks(self.width) {
+            let line = row
+                .iter()
+                .flat_map(|lit| iter::repeat_n(if *lit { 0 } else { 255 }, scale))
+                .collect::<Vec<u8>>();
+            for _ in 0..scale {
+                data.extend(&line);
+            }
+        }
+        encoder.write_header()?.write_image_data(&data)
+    }
+
+    pub fn save_pbm(&self, path: &Path) -> io::Result<()> {
+        self.write_pbm(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn save_png(&self, path: &Path, scale: usize) -> Result<(), png::EncodingError> {
+        self.write_png(BufWriter::new(File::create(path)?), scale)
+    }
+}
+
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self
+            .pixels
+            .chunks(self.width)
+            .map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect())
+            .collect::<Vec<String>>();
+        write!(f, "{}", rows.join("\n"))
     }
-    buffer
+}
+
+// runs the program drawing a pixel per cycle, calling `on_pixel` after each
+// one. Stops early if the program ends before filling the screen, and returns
+// `None` without running it if the screen has no pixels or too many.
+fn draw_with<F: FnMut(&Screen)>(
+    instructions: &[Instruction],
+    config: &CrtConfig,
+    mut on_pixel: F,
+) -> Option<Screen> {
+    let pixels = config
+        .width
+        .checked_mul(config.height)
+        .filter(|pixels| *pixels > 0 && *pixels < usize::MAX)?;
+    let mut screen = Screen::new(config.width, config.height);
+    let mut cpu = Cpu::new(instructions);
+    cpu.add_breakpoint(Breakpoint::Cycle(pixels + 1));
+    let left = (config.sprite_width as i64 - 1) / 2;
+    cpu.run(|cpu| {
+        let index = cpu.cycle() - 1;
+        let col = (index % config.width) as i64;
+        let sprite = cpu.x() - left;
+        if (sprite..sprite + config.sprite_width as i64).contains(&col) {
+            screen.pixels[index] = true;
+        }
+        on_pixel(&screen);
+    });
+    Some(screen)
+}
+
+/// Screen drawn by the program, with the sprite centered at X (for even
+/// widths the extra pixel goes to the right). Returns `None` if the screen has
+/// no pixels or too many to count.
+pub fn draw(instructions: &[Instruction], config: &CrtConfig) -> Option<Screen> {
+    draw_with(instructions, config, |_| ())
+}
+
+/// The screen after every cycle, to see how the image gets drawn. Returns
+/// `None` if the screen has no pixels or too many to count.
+pub fn draw_frames(instructions: &[Instruction], config: &CrtConfig) -> Option<Vec<Screen>> {
+    let mut frames = Vec::new();
+    draw_with(instructions, config, |screen| frames.push(screen.clone()))?;
+    Some(frames)
+}
+
+#[aoc(day10, part2)]
+fn render_crt(instructions: &[Instruction]) -> String {
+    draw(instructions, &CrtConfig::default())
+        .unwrap()
+        .to_string()
+}
+
+// The 4x6 font used by the puzzles, each glyph followed by a blank column
+const FONT: [(char, &str); 18] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Y', "#..##..#.##...#...#...#."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OcrError {
+    /// The screen isn't 6 rows of whole 5-column glyphs.
+    BadSize { width: usize, height: usize },
+    /// Glyph positions (counting from 0) that don't match any letter, along
+    /// with the text read using `?` for them.
+    Unrecognised { positions: Vec<usize>, text: String },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::BadSize { width, height } => {
+                write!(f, "Screen of {}x{} can't hold 4x6 letters", width, height)
+            }
+            OcrError::Unrecognised { positions, text } => write!(
+                f,
+                "Unrecognised glyphs at {:?} reading \"{}\"",
+                positions, text
+            ),
+        }
+    }
+}
+
+/// Reads the capital letters drawn on a CRT rendered with `#` and `.`.
+pub fn read_letters(screen: &str) -> Result<String, OcrError> {
+    let rows = screen.lines().map(|l| l.as_bytes()).collect::<Vec<&[u8]>>();
+    let width = rows.first().map_or(0, |r| r.len());
+    if rows.len() != 6 || (width + 1) % 5 > 1 || rows.iter().any(|r| r.len() != width) {
+        return Err(OcrError::BadSize {
+            width,
+            height: rows.len(),
+        });
+    }
+
+    let mut text = String::new();
+    let mut positions = Vec::new();
+    for i in 0..(width + 1) / 5 {
+        let glyph = rows
+            .iter()
+            .flat_map(|r| r[i * 5..i * 5 + 4].iter().map(|b| *b as char))
+            .collect::<String>();
+        match FONT.iter().find(|(_, g)| *g == glyph) {
+            Some((c, _)) => text.push(*c),
+            None => {
+                positions.push(i);
+                text.push('?');
+            }
+        }
+    }
+    if positions.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unrecognised { positions, text })
+    }
+}
+
+/// Part 2 answer as text instead of as a drawing.
+pub fn read_crt(instructions: &[Instruction]) -> Result<String, OcrError> {
+    read_letters(&render_crt(instructions))
 }
 
 #[cfg(test)]
@@ -259,4 +906,242 @@ noop";
         let output = render_crt(&input);
         assert_eq!(output.as_str(), DAY10_PART2_OUTPUT);
     }
+
+    #[test]
+    fn test_day10_cpu() {
+        let input = parse_input("noop\naddx 3\naddx -5");
+        let mut cpu = Cpu::new(&input).with_trace();
+        let mut xs = Vec::new();
+        assert_eq!(cpu.run(|cpu| xs.push((cpu.cycle(), cpu.x()))), Stop::Halted);
+        assert_eq!(xs, vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)]);
+        assert_eq!(cpu.x(), -1);
+        assert_eq!(cpu.cycle(), 6);
+        assert!(cpu.is_halted());
+        assert!(!cpu.step());
+        assert_eq!(cpu.trace().len(), 5);
+        assert_eq!(cpu.trace()[3], "cycle    4: X=4    addx -5 (1/2)");
+    }
+
+    #[test]
+    fn test_day10_breakpoints() {
+        let input = parse_input(DAY10_EXAMPLE);
+        let mut cpu = Cpu::new(&input);
+        cpu.add_breakpoint(Breakpoint::Cycle(20));
+        cpu.add_breakpoint(Breakpoint::Register(Register::X, 20));
+        assert_eq!(cpu.run(|_| ()), Stop::Breakpoint(Breakpoint::Cycle(20)));
+        assert_eq!(cpu.x(), 21);
+        assert_eq!(
+            cpu.current_instruction(),
+            Some(&Instruction::Add(Register::X, Operand::Value(-1)))
+        );
+
+        assert_eq!(
+            cpu.run(|_| ()),
+            Stop::Breakpoint(Breakpoint::Register(Register::X, 20))
+        );
+        assert_eq!(cpu.cycle(), 22);
+
+        cpu.remove_breakpoint(Breakpoint::Register(Register::X, 20));
+        let mut cycles = 0;
+        assert_eq!(cpu.run(|_| cycles += 1), Stop::Halted);
+        assert_eq!(cpu.cycle() - 1, 240);
+        assert_eq!(cycles, 240 - 21);
+    }
+
+    // draws `text` with the puzzle font, like the CRT would
+    fn draw_letters(text: &str) -> String {
+        (0..6)
+            .map(|row| {
+                text.chars()
+                    .map(|c| {
+                        let (_, glyph) = FONT.iter().find(|(l, _)| *l == c).unwrap();
+                        glyph[row * 4..row * 4 + 4].to_string()
+                    })
+                    .collect::<Vec<String>>()
+                    .join(".")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_day10_read_letters() {
+        let screen = draw_letters("ZKJFBJFZ");
+        assert_eq!(screen.lines().next().unwrap().len(), 39);
+        assert_eq!(read_letters(&screen), Ok("ZKJFBJFZ".to_string()));
+        // the CRT draws the trailing blank column too
+        let padded = screen
+            .lines()
+            .map(|l| format!("{}.", l))
+            .collect::<Vec<String>>();
+        assert_eq!(read_letters(&padded.join("\n")), Ok("ZKJFBJFZ".to_string()));
+        assert_eq!(
+            read_letters(&draw_letters("ABCEFGHIJKLOPRSUYZ")),
+            Ok("ABCEFGHIJKLOPRSUYZ".to_string())
+        );
+    }
+
+    #[test]
+    fn test_day10_read_letters_errors() {
+        let input = parse_input(DAY10_EXAMPLE);
+        let error = read_crt(&input).unwrap_err();
+        assert_eq!(
+            error,
+            OcrError::Unrecognised {
+                positions: (0..8).collect(),
+                text: "????????".to_string()
+            }
+        );
+
+        let mut screen = draw_letters("HELLO").replacen('#', ".", 1);
+        assert_eq!(
+            read_letters(&screen).unwrap_err().to_string(),
+            "Unrecognised glyphs at [0] reading \"?ELLO\""
+        );
+        screen.push_str("\n....");
+        assert_eq!(
+            read_letters(&screen),
+            Err(OcrError::BadSize {
+                width: 24,
+                height: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_day10_assemble() {
+        let program = assemble(
+            "; move the sprite right every other loop
+    sety 4 ; counter
+loop:
+    addx 1
+    jz y end
+    addy -1
+    jmp loop
+end: noop",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::Set(Register::Y, Operand::Value(4)),
+                Instruction::Add(Register::X, Operand::Value(1)),
+                Instruction::JumpIfZero(Register::Y, 5),
+                Instruction::Add(Register::Y, Operand::Value(-1)),
+                Instruction::Jump(1),
+                Instruction::Noop,
+            ]
+        );
+        assert_eq!(assemble("addx 2\njnz x -1"), assemble("a: addx 2\njnz x a"));
+        assert_eq!(program[2].to_string(), "jz y @5");
+
+        let mut cpu = Cpu::new(&program);
+        assert_eq!(cpu.run(|_| ()), Stop::Halted);
+        assert_eq!(cpu.x(), 6);
+        assert_eq!(cpu.register(Register::Y), 0);
+        // set + 4 full loops of 7 cycles + add, jz and noop
+        assert_eq!(cpu.cycle() - 1, 1 + 4 * 7 + 5);
+
+        let mut costs = CycleCosts::default();
+        costs.set(Opcode::Add, 1);
+        costs.set(Opcode::JumpIfZero, 0);
+        let mut cpu = Cpu::new(&program).with_costs(costs);
+        cpu.run(|_| ());
+        assert_eq!(cpu.cycle() - 1, 1 + 4 * 4 + 3);
+    }
+
+    #[test]
+    fn test_day10_assemble_errors() {
+        let error = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(
+            error("noop\nmulx 3"),
+            "Unknown instruction 'mulx' at line 2"
+        );
+        assert_eq!(error("addq 3"), "Unknown register 'q' at line 1");
+        assert_eq!(error("abé 1"), "Unknown instruction 'abé' at line 1");
+        assert_eq!(error("addé 1"), "Unknown instruction 'addé' at line 1");
+        assert_eq!(error("addx"), "'addx' takes 1 operands but got 0 at line 1");
+        assert_eq!(
+            error("addx foo"),
+            "Expected a number or a register, found 'foo' at line 1"
+        );
+        assert_eq!(error("jmp nowhere"), "Undefined label 'nowhere' at line 1");
+        assert_eq!(error("a:\na: noop"), "Label 'a' defined twice at line 2");
+        assert_eq!(
+            error("noop\njmp -3"),
+            "Offset -3 jumps outside of the program at line 2"
+        );
+    }
+
+    #[test]
+    fn test_day10_crt_config() {
+        let input = parse_input(DAY10_EXAMPLE);
+        let wide = CrtConfig {
+            width: 20,
+            height: 12,
+            sprite_width: 1,
+        };
+        let screen = draw(&input, &wide).unwrap();
+        assert_eq!((screen.width(), screen.height()), (20, 12));
+        assert_eq!(
+            screen.to_string().lines().next(),
+            Some(".#...#..#...#....#..")
+        );
+
+        let frames = draw_frames(&input, &CrtConfig::default()).unwrap();
+        assert_eq!(frames.len(), 240);
+        assert_eq!(frames[239].to_string(), DAY10_PART2_OUTPUT);
+        assert_eq!(
+            frames[1].to_string().lines().next().unwrap(),
+            format!("##{}", ".".repeat(38))
+        );
+        assert!(!frames[2].is_lit(2, 0));
+        assert!(!frames[0].is_lit(0, 1));
+
+        // the program ends before filling a bigger screen
+        let tall = CrtConfig {
+            height: 8,
+            ..CrtConfig::default()
+        };
+        assert_eq!(draw_frames(&input, &tall).unwrap().len(), 240);
+
+        for (width, height) in [(0, 6), (40, 0), (usize::MAX, 2)] {
+            let config = CrtConfig {
+                width,
+                height,
+                ..CrtConfig::default()
+            };
+            assert_eq!(draw(&input, &config), None);
+            assert_eq!(draw_frames(&input, &config), None);
+        }
+    }
+
+    #[test]
+    fn test_day10_image_export() {
+        let program = assemble("noop\naddx 2").unwrap();
+        let config = CrtConfig {
+            width: 3,
+            height: 1,
+            sprite_width: 2,
+        };
+        let screen = draw(&program, &config).unwrap();
+        assert_eq!(screen.to_string(), ".##");
+
+        let mut pbm = Vec::new();
+        screen.write_pbm(&mut pbm).unwrap();
+        assert_eq!(String::from_utf8(pbm).unwrap(), "P1\n3 1\n0 1 1\n");
+
+        let mut png = Vec::new();
+        screen.write_png(&mut png, 2).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (6, 2));
+        assert_eq!(data, [255, 255, 0, 0, 0, 0, 255, 255, 0, 0, 0, 0]);
+
+        assert!(screen.write_png(Vec::new(), 0).is_err());
+        assert!(screen.write_png(Vec::new(), usize::MAX).is_err());
+    }
 }
@@ -1,5 +1,10 @@
 use aoc_runner_derive::aoc;
 use aoc_runner_derive::aoc_generator;
+use itertools::Itertools;
+use num_bigint::BigUint;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
 
 #[derive(Clone)]
 pub struct Monkey {
@@ -25,23 +30,216 @@ impl Monkey {
         }
     }
 
-    pub fn inspect(&self, item: u64) -> u64 {
+    pub fn inspect<W: Worry>(&self, item: &W) -> Result<W, ArithmeticError> {
         self.operation.eval(item)
     }
 
-    pub fn who_to_throw(&self, item: u64) -> usize {
-        if item % self.test.divisible == 0 {
+    pub fn who_to_throw<W: Worry>(&self, item: &W) -> usize {
+        if item.rem_u64(self.test.divisible) == 0 {
             return self.test.if_true;
         }
         self.test.if_false
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithmeticError {
+    Overflow,
+    Negative,
+    DivisionByZero,
+}
+
+/// Number type used for worry levels. Operations report errors instead of
+/// wrapping around.
+pub trait Worry: Clone + fmt::Debug + fmt::Display + Eq + Hash {
+    fn from_u64(n: u64) -> Self;
+    fn add(&self, other: &Self) -> Result<Self, ArithmeticError>;
+    fn sub(&self, other: &Self) -> Result<Self, ArithmeticError>;
+    fn mul(&self, other: &Self) -> Result<Self, ArithmeticError>;
+    fn div(&self, other: &Self) -> Result<Self, ArithmeticError>;
+    fn rem_u64(&self, m: u64) -> u64;
+    fn reduce(&self, m: u64) -> Self;
+}
+
+macro_rules! impl_worry_for_primitive {
+    ($t:ty) => {
+        impl Worry for $t {
+            fn from_u64(n: u64) -> Self {
+                n as $t
+            }
+
+            fn add(&self, other: &Self) -> Result<Self, ArithmeticError> {
+                self.checked_add(*other).ok_or(ArithmeticError::Overflow)
+            }
+
+            fn sub(&self, other: &Self) -> Result<Self, ArithmeticError> {
+                self.checked_sub(*other).ok_or(ArithmeticError::Negative)
+            }
+
+            fn mul(&self, other: &Self) -> Result<Self, ArithmeticError> {
+                self.checked_mul(*other).ok_or(ArithmeticError::Overflow)
+            }
+
+            fn div(&self, other: &Self) -> Result<Self, ArithmeticError> {
+                self.checked_div(*other)
+                    .ok_or(ArithmeticError::DivisionByZero)
+            }
+
+            fn rem_u64(&self, m: u64) -> u64 {
+                (*self % m as $t) as u64
+            }
+
+            fn reduce(&self, m: u64) -> Self {
+                *self % m as $t
+            }
+        }
+    };
+}
+
+impl_worry_for_primitive!(u64);
+impl_worry_for_primitive!(u128);
+
+impl Worry for BigUint {
+    fn from_u64(n: u64) -> Self {
+        BigUint::from(n)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, ArithmeticError> {
+        Ok(self + other)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, ArithmeticError> {
+        if self < other {
+            return Err(ArithmeticError::Negative);
+        }
+        Ok(self - other)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, ArithmeticError> {
+        Ok(self * other)
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, ArithmeticError> {
+        if other.bits() == 0 {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+        Ok(self / other)
+    }
+
+    fn rem_u64(&self, m: u64) -> u64 {
+        self.reduce(m).iter_u64_digits().next().unwrap_or(0)
+    }
+
+    fn reduce(&self, m: u64) -> Self {
+        self % m
+    }
+}
+
+/// Worry number types that can be picked at runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorryType {
+    U64,
+    U128,
+    Big,
+}
+
+/// Right hand side of the `new = ...` line, parsed once per monkey.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Operation {
-    Add(u64),
-    Mul(u64),
-    Pow,
+    Old,
+    Const(u64),
+    Add(Box<Operation>, Box<Operation>),
+    Sub(Box<Operation>, Box<Operation>),
+    Mul(Box<Operation>, Box<Operation>),
+    Div(Box<Operation>, Box<Operation>),
+}
+
+// Recursive descent over the tokens of an expression, with the usual
+// precedence of `*` and `/` over `+` and `-`.
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (i, c) in input.char_indices() {
+            let is_word = c.is_ascii_alphanumeric();
+            if let Some(s) = start {
+                if !is_word {
+                    tokens.push(&input[s..i]);
+                    start = None;
+                }
+            }
+            if is_word && start.is_none() {
+                start = Some(i);
+            } else if !is_word && !c.is_whitespace() {
+                tokens.push(&input[i..i + c.len_utf8()]);
+            }
+        }
+        if let Some(s) = start {
+            tokens.push(&input[s..]);
+        }
+        Parser { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn expression(&mut self) -> Result<Operation, String> {
+        let mut left = self.term()?;
+        while let Some(op) = self.peek() {
+            let build = match op {
+                "+" => Operation::Add,
+                "-" => Operation::Sub,
+                _ => break,
+            };
+            self.next();
+            left = build(Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Operation, String> {
+        let mut left = self.factor()?;
+        while let Some(op) = self.peek() {
+            let build = match op {
+                "*" => Operation::Mul,
+                "/" => Operation::Div,
+                _ => break,
+            };
+            self.next();
+            left = build(Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Operation, String> {
+        match self.next() {
+            Some("old") => Ok(Operation::Old),
+            Some("(") => {
+                let inner = self.expression()?;
+                match self.next() {
+                    Some(")") => Ok(inner),
+                    other => Err(format!("Expected ')', found {:?}", other)),
+                }
+            }
+            Some(token) => token
+                .parse::<u64>()
+                .map(Operation::Const)
+                .map_err(|_| format!("Unexpected token '{}'", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
 }
 
 impl Operation {
@@ -53,25 +251,39 @@ impl Operation {
             .split("new = ")
             .nth(1)
             .unwrap();
-        if expression == "old * old" {
-            return Operation::Pow;
+        Operation::parse(expression)
+            .unwrap_or_else(|e| panic!("Wrong operation '{}': {}", expression, e))
+    }
+
+    /// Parses an expression made of `old`, integers, `+ - * /` and
+    /// parentheses.
+    pub fn parse(expression: &str) -> Result<Operation, String> {
+        let mut parser = Parser::new(expression);
+        let operation = parser.expression()?;
+        match parser.peek() {
+            None => Ok(operation),
+            Some(token) => Err(format!("Unexpected token '{}'", token)),
         }
-        assert!(expression.starts_with("old "));
-        let parts = expression.split(' ').collect::<Vec<&str>>();
-        let n = parts[2].parse::<u64>().unwrap();
-        let operator = parts[1];
-        match operator {
-            "+" => Operation::Add(n),
-            "*" => Operation::Mul(n),
-            _ => panic!("Wrong operator!"),
+    }
+
+    /// True if the operation is made only of sums and products, so it gives
+    /// the same remainders when `old` is reduced modulo any number.
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Operation::Old | Operation::Const(_) => true,
+            Operation::Add(a, b) | Operation::Mul(a, b) => a.is_modular() && b.is_modular(),
+            Operation::Sub(_, _) | Operation::Div(_, _) => false,
         }
     }
 
-    fn eval(&self, old: u64) -> u64 {
+    fn eval<W: Worry>(&self, old: &W) -> Result<W, ArithmeticError> {
         match self {
-            Operation::Pow => old * old,
-            Operation::Add(n) => old + n,
-            Operation::Mul(n) => old * n,
+            Operation::Old => Ok(old.clone()),
+            Operation::Const(n) => Ok(W::from_u64(*n)),
+            Operation::Add(a, b) => a.eval(old)?.add(&b.eval(old)?),
+            Operation::Sub(a, b) => a.eval(old)?.sub(&b.eval(old)?),
+            Operation::Mul(a, b) => a.eval(old)?.mul(&b.eval(old)?),
+            Operation::Div(a, b) => a.eval(old)?.div(&b.eval(old)?),
         }
     }
 }
@@ -114,31 +326,314 @@ pub fn parse_input(input: &str) -> Vec<Monkey> {
     input.split("\n\n").map(Monkey::from).collect()
 }
 
-fn get_monkey_business(input: &[Monkey], rounds: i32, relief: u64) -> u64 {
-    let mut monkeys = input.to_owned();
-    let mut throws = vec![0u64; monkeys.len()];
-    let mcd = monkeys
-        .iter()
-        .fold(1, |mcd, monkey| mcd * monkey.test.divisible);
-
-    for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            let current = &mut monkeys[i];
-            let items_to_throw = current.items.clone();
-            current.items.clear();
-            throws[i] += items_to_throw.len() as u64;
-            for item in items_to_throw {
-                let current = &monkeys[i]; // outer &mut cannot be used non-mutably
-                let new = (current.inspect(item) / relief) % mcd;
-                let throw_at = current.who_to_throw(new);
-
-                let other = &mut monkeys[throw_at];
-                other.items.push(new);
+/// Item being thrown around, along with every monkey that has held it.
+#[derive(Clone, Debug)]
+pub struct Item<W> {
+    pub id: usize,
+    pub worry: W,
+    pub path: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationError {
+    /// Arithmetic error hit by `monkey` while playing `round` (counting from
+    /// 0).
+    Arithmetic {
+        round: usize,
+        monkey: usize,
+        error: ArithmeticError,
+    },
+    /// Worries can't be reduced modulo the product of the divisors because
+    /// the operation of this monkey subtracts or divides.
+    NonModularOperation(usize),
+    /// Same for dividing by a relief other than 1.
+    NonModularRelief(u64),
+    /// Looking many rounds ahead needs the worries reduced modulo the
+    /// product of the divisors, otherwise they may never repeat.
+    Unbounded,
+}
+
+// reducing worries modulo the product of the divisors only keeps the
+// divisibility tests right when every step is made of sums and products
+fn check_modular(input: &[Monkey], relief: u64) -> Result<(), SimulationError> {
+    if let Some(monkey) = input.iter().position(|m| !m.operation.is_modular()) {
+        return Err(SimulationError::NonModularOperation(monkey));
+    }
+    if relief != 1 {
+        return Err(SimulationError::NonModularRelief(relief));
+    }
+    Ok(())
+}
+
+/// Monkeys passing items round after round. By default worries are reduced
+/// modulo the product of the divisors so they don't grow forever.
+pub struct Simulation<W: Worry = u64> {
+    monkeys: Vec<Monkey>,
+    holding: Vec<Vec<Item<W>>>,
+    inspections: Vec<u64>,
+    round: usize,
+    relief: u64,
+    mcd: Option<u64>,
+    track_paths: bool,
+}
+
+impl Simulation<u64> {
+    /// Items get an id in the order they appear in the input. Paths are only
+    /// recorded if `track_paths` is set, since they grow every round. Worries
+    /// are reduced modulo the product of the divisors unless the operations
+    /// or the relief don't allow it.
+    pub fn new(input: &[Monkey], relief: u64, track_paths: bool) -> Simulation {
+        let modulo = check_modular(input, relief).is_ok();
+        Simulation::with_worry(input, relief, track_paths, modulo).unwrap()
+    }
+}
+
+impl<W: Worry> Simulation<W> {
+    /// Like `new` but with any worry type, and optionally without reducing
+    /// worries modulo the product of the divisors. Fails if `modulo` is set
+    /// but the operations or the relief don't allow it.
+    pub fn with_worry(
+        input: &[Monkey],
+        relief: u64,
+        track_paths: bool,
+        modulo: bool,
+    ) -> Result<Simulation<W>, SimulationError> {
+        if modulo {
+            check_modular(input, relief)?;
+        }
+        let mut id = 0;
+        let holding = input
+            .iter()
+            .enumerate()
+            .map(|(i, monkey)| {
+                monkey
+                    .items
+                    .iter()
+                    .map(|worry| {
+                        id += 1;
+                        Item {
+                            id: id - 1,
+                            worry: W::from_u64(*worry),
+                            path: vec![i],
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Simulation {
+            monkeys: input.to_owned(),
+            holding,
+            inspections: vec![0; input.len()],
+            round: 0,
+            relief,
+            mcd: if modulo {
+                Some(
+                    input
+                        .iter()
+                        .fold(1, |mcd, monkey| mcd * monkey.test.divisible),
+                )
+            } else {
+                None
+            },
+            track_paths,
+        })
+    }
+
+    /// Number of rounds played so far.
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    // new worry of an item inspected by `monkey`, and who gets it next
+    fn throw(&self, monkey: usize, worry: &W) -> Result<(W, usize), SimulationError> {
+        let current = &self.monkeys[monkey];
+        let new = current
+            .inspect(worry)
+            .and_then(|w| w.div(&W::from_u64(self.relief)))
+            .map_err(|error| SimulationError::Arithmetic {
+                round: self.round,
+                monkey,
+                error,
+            })?;
+        let new = match self.mcd {
+            Some(mcd) => new.reduce(mcd),
+            None => new,
+        };
+        let throw_at = current.who_to_throw(&new);
+        Ok((new, throw_at))
+    }
+
+    /// Plays a round. After an error the simulation is left half way through
+    /// the round and shouldn't be used anymore.
+    pub fn step(&mut self) -> Result<(), SimulationError> {
+        for i in 0..self.monkeys.len() {
+            let items_to_throw = std::mem::take(&mut self.holding[i]);
+            self.inspections[i] += items_to_throw.len() as u64;
+            for mut item in items_to_throw {
+                let throw_at;
+                (item.worry, throw_at) = self.throw(i, &item.worry)?;
+                if self.track_paths {
+                    item.path.push(throw_at);
+                }
+                self.holding[throw_at].push(item);
+            }
+        }
+        self.round += 1;
+        Ok(())
+    }
+
+    pub fn run(&mut self, rounds: usize) -> Result<(), SimulationError> {
+        for _ in 0..rounds {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// How many items each monkey has inspected so far.
+    pub fn inspections(&self) -> &[u64] {
+        &self.inspections
+    }
+
+    /// Worry levels of the items held by `monkey`, in the order it will
+    /// inspect them.
+    pub fn holding(&self, monkey: usize) -> Vec<W> {
+        self.holding[monkey]
+            .iter()
+            .map(|item| item.worry.clone())
+            .collect()
+    }
+
+    /// Inspection counts after playing `rounds` more rounds, without
+    /// simulating all of them. Items don't affect each other, so each one is
+    /// followed on its own until it's back at the same monkey with the same
+    /// worry at the start of a round, and from there its inspections repeat.
+    /// Only works when worries are reduced modulo the product of the
+    /// divisors.
+    pub fn inspections_after(&self, rounds: u64) -> Result<Vec<u64>, SimulationError> {
+        if self.mcd.is_none() {
+            return Err(SimulationError::Unbounded);
+        }
+        let mut total = self.inspections.clone();
+        for (monkey, items) in self.holding.iter().enumerate() {
+            for item in items {
+                let counts = self.item_inspections((monkey, item.worry.clone()), rounds)?;
+                for (t, c) in total.iter_mut().zip(counts) {
+                    *t += c;
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    fn item_inspections(
+        &self,
+        mut state: (usize, W),
+        rounds: u64,
+    ) -> Result<Vec<u64>, SimulationError> {
+        let mut seen = HashMap::new();
+        // inspections by each monkey before the start of every round
+        let mut history = vec![vec![0u64; self.monkeys.len()]];
+        for round in 0..rounds {
+            if let Some(start) = seen.insert(state.clone(), round) {
+                let (start, len) = (start as usize, round - start);
+                let (cycles, rest) = ((rounds - start as u64) / len, (rounds - start as u64) % len);
+                let end = history.len() - 1;
+                return Ok((0..self.monkeys.len())
+                    .map(|m| {
+                        let per_cycle = history[end][m] - history[start][m];
+                        let partial = history[start + rest as usize][m] - history[start][m];
+                        history[start][m] + cycles * per_cycle + partial
+                    })
+                    .collect());
+            }
+
+            let mut counts = history.last().unwrap().clone();
+            let (mut monkey, mut worry) = state;
+            loop {
+                counts[monkey] += 1;
+                let (new, throw_at) = self.throw(monkey, &worry)?;
+                worry = new;
+                // monkeys that already played this round will get it next one
+                let next_round = throw_at <= monkey;
+                monkey = throw_at;
+                if next_round {
+                    break;
+                }
             }
+            state = (monkey, worry);
+            history.push(counts);
         }
+        Ok(history.pop().unwrap())
+    }
+
+    /// Every item, sorted by id.
+    pub fn items(&self) -> Vec<&Item<W>> {
+        let mut items = self.holding.iter().flatten().collect::<Vec<&Item<W>>>();
+        items.sort_by_key(|item| item.id);
+        items
+    }
+
+    /// Product of the two largest inspection counts.
+    pub fn monkey_business(&self) -> u64 {
+        let mut throws = self.inspections.clone();
+        throws.sort();
+        throws[throws.len() - 2] * throws[throws.len() - 1]
+    }
+
+    /// Items held by each monkey, like the listings in the puzzle.
+    pub fn report_items(&self) -> String {
+        let mut lines = vec![format!(
+            "After round {}, the monkeys are holding items with these worry levels:",
+            self.round
+        )];
+        for i in 0..self.monkeys.len() {
+            let items = self.holding(i).iter().map(|w| w.to_string()).join(", ");
+            lines.push(format!("Monkey {}: {}", i, items));
+        }
+        lines.join("\n")
+    }
+
+    /// Inspection counts, like the listings of the puzzle's second part.
+    pub fn report_inspections(&self) -> String {
+        let mut lines = vec![format!("== After round {} ==", self.round)];
+        for (i, count) in self.inspections.iter().enumerate() {
+            lines.push(format!("Monkey {} inspected items {} times.", i, count));
+        }
+        lines.join("\n")
+    }
+}
+
+fn run_simulation<W: Worry>(
+    input: &[Monkey],
+    rounds: usize,
+    relief: u64,
+    modulo: bool,
+) -> Result<u64, SimulationError> {
+    let mut simulation = Simulation::<W>::with_worry(input, relief, false, modulo)?;
+    simulation.run(rounds)?;
+    Ok(simulation.monkey_business())
+}
+
+/// Monkey business after `rounds`, using the given worry type and reducing
+/// worries modulo the product of the divisors only when `modulo` is set,
+/// which fails if some operation subtracts or divides, or the relief isn't 1.
+pub fn monkey_business(
+    input: &[Monkey],
+    rounds: usize,
+    relief: u64,
+    modulo: bool,
+    worry: WorryType,
+) -> Result<u64, SimulationError> {
+    match worry {
+        WorryType::U64 => run_simulation::<u64>(input, rounds, relief, modulo),
+        WorryType::U128 => run_simulation::<u128>(input, rounds, relief, modulo),
+        WorryType::Big => run_simulation::<BigUint>(input, rounds, relief, modulo),
     }
-    throws.sort();
-    throws[throws.len() - 2] * throws[throws.len() - 1]
+}
+
+fn get_monkey_business(input: &[Monkey], rounds: usize, relief: u64) -> u64 {
+    let modulo = relief == 1;
+    monkey_business(input, rounds, relief, modulo, WorryType::U64).unwrap()
 }
 
 #[aoc(day11, part1)]
@@ -194,4 +689,212 @@ Monkey 3:
         let input = parse_input(DAY11_EXAMPLE);
         assert_eq!(get_monkey_business_after_10krounds(&input), 2713310158);
     }
+
+    #[test]
+    fn test_day11_operation() {
+        let op = Operation::parse("old * old").unwrap();
+        assert_eq!(
+            op,
+            Operation::Mul(Box::new(Operation::Old), Box::new(Operation::Old))
+        );
+        assert_eq!(op.eval(&7u64), Ok(49));
+        assert_eq!(Operation::parse("old + 6").unwrap().eval(&1u64), Ok(7));
+        assert_eq!(Operation::parse("2 + old * 3").unwrap().eval(&4u64), Ok(14));
+        assert_eq!(
+            Operation::parse("(2 + old) * 3").unwrap().eval(&4u64),
+            Ok(18)
+        );
+        assert_eq!(
+            Operation::parse("old - 10 - 2").unwrap().eval(&20u64),
+            Ok(8)
+        );
+        assert_eq!(
+            Operation::parse("old / (4 / 2)").unwrap().eval(&20u64),
+            Ok(10)
+        );
+        assert_eq!(Operation::parse("5").unwrap().eval(&20u64), Ok(5));
+
+        assert!(Operation::parse("old +").is_err());
+        assert!(Operation::parse("(old * 2").is_err());
+        assert!(Operation::parse("old old").is_err());
+        assert!(Operation::parse("old ^ 2").is_err());
+    }
+
+    #[test]
+    fn test_day11_modular_operations() {
+        assert!(Operation::parse("(old + 3) * old * 2")
+            .unwrap()
+            .is_modular());
+        assert!(!Operation::parse("old * 19 - 5").unwrap().is_modular());
+        assert!(!Operation::parse("old * (old / 2)").unwrap().is_modular());
+
+        let mut input = parse_input(DAY11_EXAMPLE);
+        input[0].operation = Operation::parse("old * 19 - 5").unwrap();
+        input[2].operation = Operation::parse("old * old / 2").unwrap();
+        assert_eq!(
+            monkey_business(&input, 20, 1, true, WorryType::U64),
+            Err(SimulationError::NonModularOperation(0))
+        );
+        assert!(Simulation::<BigUint>::with_worry(&input, 1, false, true).is_err());
+
+        // without the modulo trick the result matches the exact one
+        let exact = monkey_business(&input, 20, 1, false, WorryType::Big);
+        assert_eq!(exact, Ok(9800));
+        let mut simulation = Simulation::new(&input, 1, false);
+        let mut big = Simulation::<BigUint>::with_worry(&input, 1, false, false).unwrap();
+        simulation.run(5).unwrap();
+        big.run(5).unwrap();
+        assert_eq!(simulation.inspections(), big.inspections());
+    }
+
+    #[test]
+    fn test_day11_report_items() {
+        let input = parse_input(DAY11_EXAMPLE);
+        let mut simulation = Simulation::new(&input, 3, false);
+        simulation.step().unwrap();
+        assert_eq!(
+            simulation.report_items(),
+            "After round 1, the monkeys are holding items with these worry levels:
+Monkey 0: 20, 23, 27, 26
+Monkey 1: 2080, 25, 167, 207, 401, 1046
+Monkey 2: 
+Monkey 3: "
+        );
+        simulation.run(19).unwrap();
+        assert_eq!(simulation.holding(0), vec![10, 12, 14, 26, 34]);
+        assert_eq!(simulation.inspections(), &[101, 95, 7, 105]);
+    }
+
+    #[test]
+    fn test_day11_report_inspections() {
+        let input = parse_input(DAY11_EXAMPLE);
+        let mut simulation = Simulation::new(&input, 1, false);
+        simulation.run(20).unwrap();
+        assert_eq!(
+            simulation.report_inspections(),
+            "== After round 20 ==
+Monkey 0 inspected items 99 times.
+Monkey 1 inspected items 97 times.
+Monkey 2 inspected items 8 times.
+Monkey 3 inspected items 103 times."
+        );
+    }
+
+    #[test]
+    fn test_day11_item_paths() {
+        let input = parse_input(DAY11_EXAMPLE);
+        let mut simulation = Simulation::new(&input, 3, true);
+        simulation.step().unwrap();
+        let items = simulation.items();
+        assert_eq!(items.len(), 10);
+        // 79 goes from monkey 0 to 3, then monkey 3 throws it to 1
+        assert_eq!(items[0].path, vec![0, 3, 1]);
+        assert_eq!(items[0].worry, 167);
+        let inspected = items.iter().map(|item| item.path.len() - 1).sum::<usize>();
+        assert_eq!(
+            inspected as u64,
+            simulation.inspections().iter().sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_day11_inspections_after() {
+        let input = parse_input(DAY11_EXAMPLE);
+        for rounds in [20, 1000, 10_000] {
+            let start = Simulation::new(&input, 1, false);
+            let mut simulation = Simulation::new(&input, 1, false);
+            simulation.run(rounds).unwrap();
+            assert_eq!(
+                start.inspections_after(rounds as u64).unwrap(),
+                simulation.inspections()
+            );
+        }
+
+        // exact worries may never repeat
+        let exact = Simulation::<BigUint>::with_worry(&input, 1, false, false).unwrap();
+        assert_eq!(
+            exact.inspections_after(1_000_000_000_000),
+            Err(SimulationError::Unbounded)
+        );
+        let relief = Simulation::new(&input, 3, false);
+        assert_eq!(
+            relief.inspections_after(20),
+            Err(SimulationError::Unbounded)
+        );
+
+        // it can also continue from a simulation already in progress
+        let mut simulation = Simulation::new(&input, 1, false);
+        simulation.run(500).unwrap();
+        assert_eq!(
+            simulation.inspections_after(500),
+            Ok(vec![5204, 4792, 199, 5192])
+        );
+
+        let rounds = 1_000_000_000_000;
+        let counts = Simulation::new(&input, 1, false)
+            .inspections_after(rounds)
+            .unwrap();
+        assert!(counts.iter().all(|c| *c > 100_000_000_000));
+        let mut simulation = Simulation::new(&input, 1, false);
+        simulation.run(10_000).unwrap();
+        assert_eq!(simulation.inspections_after(rounds - 10_000), Ok(counts));
+    }
+
+    #[test]
+    fn test_day11_worry_types() {
+        let input = parse_input(DAY11_EXAMPLE);
+        // relief keeps the worries small enough for u64 even without modulo
+        for worry in [WorryType::U64, WorryType::U128, WorryType::Big] {
+            assert_eq!(monkey_business(&input, 20, 3, false, worry), Ok(10605));
+            assert_eq!(
+                monkey_business(&input, 1000, 1, true, worry),
+                Ok(5204 * 5192)
+            );
+        }
+        assert_eq!(
+            monkey_business(&input, 20, 3, true, WorryType::U64),
+            Err(SimulationError::NonModularRelief(3))
+        );
+
+        // without relief nor modulo the worries overflow after a few rounds
+        let round = |worry| match monkey_business(&input, 1000, 1, false, worry) {
+            Err(SimulationError::Arithmetic {
+                round,
+                error: ArithmeticError::Overflow,
+                ..
+            }) => round,
+            result => panic!("Unexpected {:?}", result),
+        };
+        assert!(round(WorryType::U128) > round(WorryType::U64));
+
+        // the modulo trick doesn't change who gets each item
+        let mut exact = Simulation::<BigUint>::with_worry(&input, 1, true, false).unwrap();
+        let mut reduced = Simulation::new(&input, 1, true);
+        exact.run(20).unwrap();
+        reduced.run(20).unwrap();
+        assert_eq!(exact.inspections(), reduced.inspections());
+        for (a, b) in exact.items().iter().zip(reduced.items()) {
+            assert_eq!(a.path, b.path);
+        }
+    }
+
+    #[test]
+    fn test_day11_negative_worry() {
+        let mut input = parse_input(DAY11_EXAMPLE);
+        input[0].operation = Operation::parse("old - 100").unwrap();
+        // a reduced worry going negative says nothing about the real one
+        assert_eq!(
+            monkey_business(&input, 20, 1, true, WorryType::U64),
+            Err(SimulationError::NonModularOperation(0))
+        );
+        let error = Simulation::new(&input, 1, false).step().unwrap_err();
+        assert_eq!(
+            error,
+            SimulationError::Arithmetic {
+                round: 0,
+                monkey: 0,
+                error: ArithmeticError::Negative
+            }
+        );
+    }
 }
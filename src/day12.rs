@@ -29,11 +29,70 @@ fn diff_height(current: char, other: char) -> i32 {
     (fix_start_end(other) as i32) - (fix_start_end(current) as i32)
 }
 
-fn h_func(pos: (i32, i32), goal: (i32, i32)) -> u32 {
-    (goal.0 - pos.0).unsigned_abs() + (goal.1 - pos.1).unsigned_abs()
+/// Which steps are allowed between two cells and how much they cost, given
+/// the height difference of the step (positive when climbing).
+#[derive(Clone, Copy)]
+pub struct ClimbRules {
+    pub max_climb: i32,
+    pub max_descent: Option<i32>,
+    pub diagonal: bool,
+    pub cost: fn(i32) -> u64,
 }
 
-fn find_path(grid: &Grid<char>, start: (i32, i32), end: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+impl Default for ClimbRules {
+    /// The puzzle rules: climb at most one level, descend freely, every step
+    /// costs the same.
+    fn default() -> Self {
+        ClimbRules {
+            max_climb: 1,
+            max_descent: None,
+            diagonal: false,
+            cost: |_| 1,
+        }
+    }
+}
+
+impl ClimbRules {
+    fn allows(&self, diff: i32) -> bool {
+        diff <= self.max_climb && self.max_descent.is_none_or(|max| -diff <= max)
+    }
+
+    fn neighbors<T: Clone>(&self, grid: &Grid<T>, pos: (i32, i32)) -> Vec<(T, (usize, usize))> {
+        if self.diagonal {
+            grid.neighbors8_at(pos.0, pos.1)
+        } else {
+            grid.neighbors_at(pos.0, pos.1)
+        }
+    }
+
+    // cheapest possible step between any two heights, used to keep the
+    // heuristic admissible
+    fn min_cost(&self) -> u64 {
+        (-25..=25)
+            .filter(|diff| self.allows(*diff))
+            .map(self.cost)
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn h_func(&self, pos: (i32, i32), goal: (i32, i32)) -> u64 {
+        let (dx, dy) = (
+            (goal.0 - pos.0).unsigned_abs(),
+            (goal.1 - pos.1).unsigned_abs(),
+        );
+        let steps = if self.diagonal { dx.max(dy) } else { dx + dy };
+        steps as u64 * self.min_cost()
+    }
+}
+
+/// Cheapest route from `start` to `end`, returning its cost and the cells
+/// visited.
+pub fn find_path(
+    grid: &Grid<char>,
+    start: (i32, i32),
+    end: (i32, i32),
+    rules: &ClimbRules,
+) -> Option<(u64, Vec<(i32, i32)>)> {
     let mut open_set = DoublePriorityQueue::new();
     open_set.push(start, 0);
 
@@ -45,7 +104,7 @@ fn find_path(grid: &Grid<char>, start: (i32, i32), end: (i32, i32)) -> Option<Ve
     g_score.insert(start, 0u64);
 
     let mut f_score = BTreeMap::new();
-    f_score.insert(start, h_func(start, end) as u64);
+    f_score.insert(start, rules.h_func(start, end));
 
     while !open_set.is_empty() {
         // get the element with smallest fScore
@@ -62,24 +121,24 @@ fn find_path(grid: &Grid<char>, start: (i32, i32), end: (i32, i32)) -> Option<Ve
                 path.push(path_node);
             }
             path.reverse();
-            return Some(path);
+            return Some((g_score[&end], path));
         }
 
         closed_set.insert(current);
 
         let current_height = grid.cell_at(current.0, current.1).unwrap();
-        for (height, cell_pos) in grid.neighbors_at(current.0, current.1) {
+        for (height, cell_pos) in rules.neighbors(grid, current) {
             let candidate = (cell_pos.0 as i32, cell_pos.1 as i32);
             if closed_set.contains(&candidate) {
                 continue;
             }
 
-            // apply constraint of either going up at most one (or downhill)
-            if diff_height(current_height, height) > 1 {
+            let diff = diff_height(current_height, height);
+            if !rules.allows(diff) {
                 continue;
             }
 
-            let g_func = 1;
+            let g_func = (rules.cost)(diff);
             let tentative_g_score = g_score.entry(current).or_insert(inf).to_owned() + g_func;
 
             let neighbor_score = g_score.entry(candidate).or_insert(inf).to_owned();
@@ -91,7 +150,7 @@ fn find_path(grid: &Grid<char>, start: (i32, i32), end: (i32, i32)) -> Option<Ve
                     .and_modify(|e| *e = tentative_g_score)
                     .or_insert(tentative_g_score);
 
-                let score = tentative_g_score + h_func(candidate, end) as u64;
+                let score = tentative_g_score + rules.h_func(candidate, end);
                 f_score
                     .entry(candidate)
                     .and_modify(|e| *e = score)
@@ -114,31 +173,126 @@ fn find_cell(value: char, input: &Grid<char>) -> (i32, i32) {
 fn find_shortest_path_len(input: &Grid<char>) -> u64 {
     let start = find_cell('S', input);
     let end = find_cell('E', input);
-    if let Some(path) = find_path(input, start, end) {
-        return (path.len() - 1) as u64;
+    if let Some((cost, _)) = find_path(input, start, end, &ClimbRules::default()) {
+        return cost;
     }
     0
 }
 
-#[aoc(day12, part2)]
-fn find_shortest_path_any_a(input: &Grid<char>) -> u64 {
-    let (width, height) = input.size();
-    let end = find_cell('E', input);
-    let mut min_dist = u64::MAX;
-    for y in 0..height as i32 {
-        for x in 0..width as i32 {
-            if input.cell_at(x, y).unwrap() != 'a' && input.cell_at(x, y).unwrap() != 'S' {
+/// Cost of the cheapest route from every cell to a common goal, along with
+/// the next step to take from each cell.
+pub struct DistanceField {
+    pub costs: Grid<Option<u64>>,
+    next: Grid<Option<(usize, usize)>>,
+}
+
+impl DistanceField {
+    /// Follows the next steps from `start` until reaching the goal.
+    pub fn path_from(&self, start: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        self.costs.cell_at(start.0, start.1)??;
+        let mut current = start;
+        let mut path = vec![current];
+        while let Some(next) = self.next.cell_at(current.0, current.1).unwrap() {
+            current = (next.0 as i32, next.1 as i32);
+            path.push(current);
+        }
+        Some(path)
+    }
+}
+
+/// Cost from every cell to `end`, computed with a single Dijkstra search
+/// walking the climbing rules backwards. Unreachable cells are `None`.
+pub fn distances_to(grid: &Grid<char>, end: (i32, i32), rules: &ClimbRules) -> DistanceField {
+    let (width, height) = grid.size();
+    let mut costs = Grid::new(&vec![None; width * height], width);
+    let mut next = Grid::new(&vec![None; width * height], width);
+    costs.set_at(end.0 as usize, end.1 as usize, Some(0));
+
+    let mut open_set = DoublePriorityQueue::new();
+    open_set.push(end, 0);
+    let mut closed_set = BTreeSet::new();
+    while let Some((current, current_cost)) = open_set.pop_min() {
+        if !closed_set.insert(current) {
+            continue;
+        }
+        let current_height = grid.cell_at(current.0, current.1).unwrap();
+        for (height, cell_pos) in rules.neighbors(grid, current) {
+            let candidate = (cell_pos.0 as i32, cell_pos.1 as i32);
+            if closed_set.contains(&candidate) {
                 continue;
             }
-            if let Some(path) = find_path(input, (x as i32, y as i32), end) {
-                let steps = (path.len() - 1) as u64;
-                if steps < min_dist {
-                    min_dist = steps
-                }
+            // the neighbor must be able to step onto us
+            let diff = diff_height(height, current_height);
+            if !rules.allows(diff) {
+                continue;
+            }
+            let cost = current_cost + (rules.cost)(diff);
+            if costs
+                .cell_at(candidate.0, candidate.1)
+                .unwrap()
+                .is_none_or(|c| cost < c)
+            {
+                costs.set_at(cell_pos.0, cell_pos.1, Some(cost));
+                next.set_at(
+                    cell_pos.0,
+                    cell_pos.1,
+                    Some((current.0 as usize, current.1 as usize)),
+                );
+                open_set.push(candidate, cost);
             }
         }
     }
-    min_dist as u64
+    DistanceField { costs, next }
+}
+
+/// Draws the path over the heightmap with arrows like the puzzle does.
+pub fn render_path(grid: &Grid<char>, path: &[(i32, i32)]) -> String {
+    let (width, height) = grid.size();
+    let mut canvas = Grid::new(&vec!['.'; width * height], width);
+    for step in path.windows(2) {
+        let arrow = match (step[1].0 - step[0].0, step[1].1 - step[0].1) {
+            (1, 0) => '>',
+            (-1, 0) => '<',
+            (0, 1) => 'v',
+            (0, -1) => '^',
+            (1, 1) => '↘',
+            (1, -1) => '↗',
+            (-1, 1) => '↙',
+            (-1, -1) => '↖',
+            _ => panic!("Unexpected step {:?} -> {:?}", step[0], step[1]),
+        };
+        canvas.set_at(step[0].0 as usize, step[0].1 as usize, arrow);
+    }
+    if let Some(last) = path.last() {
+        canvas.set_at(last.0 as usize, last.1 as usize, 'E');
+    }
+
+    let mut ret = String::new();
+    for row in canvas.cells.chunks(width) {
+        ret.extend(row);
+        ret.push('\n');
+    }
+    ret
+}
+
+fn closest_lowest_cell(input: &Grid<char>, distances: &DistanceField) -> Option<(i32, i32)> {
+    let (width, _) = input.size();
+    input
+        .cells
+        .iter()
+        .zip(distances.costs.cells.iter())
+        .enumerate()
+        .filter(|(_, (c, d))| fix_start_end(**c) == 'a' && d.is_some())
+        .min_by_key(|(_, (_, d))| d.unwrap())
+        .map(|(index, _)| ((index % width) as i32, (index / width) as i32))
+}
+
+#[aoc(day12, part2)]
+fn find_shortest_path_any_a(input: &Grid<char>) -> u64 {
+    let end = find_cell('E', input);
+    let distances = distances_to(input, end, &ClimbRules::default());
+    let start = closest_lowest_cell(input, &distances).unwrap();
+    distances.costs.cell_at(start.0, start.1).unwrap().unwrap()
 }
 
 #[cfg(test)]
@@ -162,4 +316,53 @@ abdefghi";
         let input = parse_input(DAY12_EXAMPLE);
         assert_eq!(find_shortest_path_any_a(&input), 29);
     }
+
+    #[test]
+    fn test_day12_distances() {
+        let input = parse_input(DAY12_EXAMPLE);
+        let distances = distances_to(&input, find_cell('E', &input), &ClimbRules::default());
+        assert_eq!(distances.costs.cell_at(0, 0).unwrap(), Some(31));
+        assert_eq!(distances.costs.cell_at(5, 2).unwrap(), Some(0));
+        assert_eq!(closest_lowest_cell(&input, &distances), Some((0, 4)));
+    }
+
+    #[test]
+    fn test_day12_render_path() {
+        let input = parse_input(DAY12_EXAMPLE);
+        let distances = distances_to(&input, find_cell('E', &input), &ClimbRules::default());
+        let path = distances.path_from(find_cell('S', &input)).unwrap();
+        assert_eq!(path.len(), 32);
+        let rendered = render_path(&input, &path);
+        assert_eq!(rendered.matches(|c| "<>^v".contains(c)).count(), 31);
+        assert_eq!(rendered.lines().nth(2).unwrap().find('E'), Some(5));
+    }
+
+    #[test]
+    fn test_day12_climb_rules() {
+        let input = parse_input(DAY12_EXAMPLE);
+        let start = find_cell('S', &input);
+        let end = find_cell('E', &input);
+
+        let diagonal = ClimbRules {
+            diagonal: true,
+            ..ClimbRules::default()
+        };
+        let (steps, _) = find_path(&input, start, end, &diagonal).unwrap();
+        assert!(steps < 31);
+
+        let expensive_climb = ClimbRules {
+            cost: |diff| if diff > 0 { 10 } else { 1 },
+            ..ClimbRules::default()
+        };
+        let (cost, path) = find_path(&input, start, end, &expensive_climb).unwrap();
+        let field = distances_to(&input, end, &expensive_climb);
+        assert_eq!(field.costs.cell_at(start.0, start.1).unwrap(), Some(cost));
+        assert!(path.len() > 31);
+
+        let no_climb = ClimbRules {
+            max_climb: 0,
+            ..ClimbRules::default()
+        };
+        assert_eq!(find_path(&input, start, end, &no_climb), None);
+    }
 }
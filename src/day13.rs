@@ -1,14 +1,164 @@
 use aoc_runner_derive::aoc;
 use aoc_runner_derive::aoc_generator;
+use serde_json::Value;
 use std::cmp;
 use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::io::BufRead;
+use std::str;
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug)]
 pub enum PacketData {
     Integer(i64),
     List(Vec<PacketData>),
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset in the input where the problem was found.
+    pub position: usize,
+    pub reason: String,
+}
+
+impl ParseError {
+    fn new(position: usize, reason: &str) -> ParseError {
+        ParseError {
+            position,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Integer(i64),
+}
+
+/// Splits a packet into brackets and integers, checking along the way that
+/// they make a single well formed value.
+struct Tokenizer<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+    // true after '[' or ',' (and at the start), when a value must follow
+    expect_value: bool,
+    after_open: bool,
+    done: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
+            input: input.as_bytes(),
+            pos: 0,
+            depth: 0,
+            expect_value: true,
+            after_open: false,
+            done: false,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, ParseError> {
+        let start = self.pos;
+        if self.input[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        let digits = self.pos;
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+        if self.pos == digits {
+            return Err(ParseError::new(digits, "expected a digit"));
+        }
+        std::str::from_utf8(&self.input[start..self.pos])
+            .unwrap()
+            .parse()
+            .map_err(|_| ParseError::new(start, "integer too large"))
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, ParseError> {
+        loop {
+            self.skip_whitespace();
+            let pos = self.pos;
+            let c = match self.input.get(pos) {
+                Some(c) => *c,
+                None if self.depth > 0 => return Err(ParseError::new(pos, "unclosed list")),
+                None if self.expect_value => return Err(ParseError::new(pos, "expected a value")),
+                None => return Ok(None),
+            };
+            if self.depth == 0 && !self.expect_value {
+                return Err(ParseError::new(pos, "unexpected trailing characters"));
+            }
+            match c {
+                b'[' if self.expect_value => {
+                    self.pos += 1;
+                    self.depth += 1;
+                    self.after_open = true;
+                    return Ok(Some(Token::Open));
+                }
+                b']' if !self.expect_value || self.after_open => {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    self.expect_value = false;
+                    self.after_open = false;
+                    return Ok(Some(Token::Close));
+                }
+                b',' if !self.expect_value => {
+                    self.pos += 1;
+                    self.expect_value = true;
+                }
+                b'-' | b'0'..=b'9' if self.expect_value => {
+                    let n = self.integer()?;
+                    self.expect_value = false;
+                    self.after_open = false;
+                    return Ok(Some(Token::Integer(n)));
+                }
+                b'[' | b'-' | b'0'..=b'9' => {
+                    return Err(ParseError::new(pos, "expected ',' or ']'"))
+                }
+                b']' | b',' => return Err(ParseError::new(pos, "expected a value")),
+                _ => return Err(ParseError::new(pos, "unexpected character")),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_token() {
+            Ok(Some(token)) => Some(Ok(token)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for PacketData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut buffer = "".to_string();
@@ -29,34 +179,45 @@ impl std::fmt::Display for PacketData {
     }
 }
 
-impl PacketData {
-    fn from(input: &str) -> PacketData {
-        if input.starts_with('[') {
-            // List
-            let mut values = Vec::new();
-            let input = &input[1..input.len() - 1];
-            let mut start = 0;
-            let mut level = 0;
-            for (index, c) in input.char_indices() {
-                match c {
-                    '[' => level += 1,
-                    ']' => level -= 1,
-                    ',' => {
-                        if level == 0 {
-                            values.push(Self::from(&input[start..index]));
-                            start = index + 1;
-                        }
-                    }
-                    _ => (),
+impl str::FromStr for PacketData {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<PacketData, ParseError> {
+        let mut lists: Vec<Vec<PacketData>> = Vec::new();
+        let mut value = None;
+        for token in Tokenizer::new(input) {
+            let item = match token? {
+                Token::Open => {
+                    lists.push(Vec::new());
+                    continue;
                 }
+                Token::Close => PacketData::List(lists.pop().unwrap()),
+                Token::Integer(n) => PacketData::Integer(n),
+            };
+            match lists.last_mut() {
+                Some(list) => list.push(item),
+                None => value = Some(item),
             }
-            if start < input.len() {
-                values.push(Self::from(&input[start..input.len()]));
-            }
-            PacketData::List(values)
-        } else {
-            // Integer
-            PacketData::Integer(input.parse().unwrap())
+        }
+        // the tokenizer guarantees a single complete value
+        Ok(value.unwrap())
+    }
+}
+
+impl PacketData {
+    /// Panics if the packet isn't valid, use `parse` to get the error.
+    fn from(input: &str) -> PacketData {
+        input
+            .parse()
+            .unwrap_or_else(|e| panic!("Invalid packet {}: {}", input, e))
+    }
+
+    // the integer this packet is equal to, if any
+    fn as_integer(&self) -> Option<i64> {
+        match self {
+            PacketData::Integer(n) => Some(*n),
+            PacketData::List(list) if list.len() == 1 => list[0].as_integer(),
+            PacketData::List(_) => None,
         }
     }
 
@@ -68,44 +229,286 @@ impl PacketData {
     }
 }
 
-impl PartialOrd for PacketData {
-    fn partial_cmp(&self, other: &PacketData) -> Option<cmp::Ordering> {
+impl Ord for PacketData {
+    fn cmp(&self, other: &PacketData) -> cmp::Ordering {
         match (self, other) {
             (PacketData::Integer(_), PacketData::List(_)) => {
-                return Self::partial_cmp(&self.as_list(), other)
+                return Self::cmp(&self.as_list(), other)
             }
             (PacketData::List(_), PacketData::Integer(_)) => {
-                return Self::partial_cmp(self, &other.as_list())
+                return Self::cmp(self, &other.as_list())
             }
             _ => (),
         }
 
         match (self, other) {
-            (PacketData::Integer(n), PacketData::Integer(m)) => n.partial_cmp(m),
+            (PacketData::Integer(n), PacketData::Integer(m)) => n.cmp(m),
             (PacketData::List(left_list), PacketData::List(right_list)) => {
                 for (left, right) in left_list.iter().zip(right_list.iter()) {
-                    if let Some(comparison) = left.partial_cmp(right) {
-                        if comparison == cmp::Ordering::Equal {
-                            continue;
-                        }
-                        return Some(comparison);
+                    let comparison = left.cmp(right);
+                    if comparison == cmp::Ordering::Equal {
+                        continue;
                     }
+                    return comparison;
                 }
                 if right_list.len() > left_list.len() {
                     // left list ran out of items
-                    return Some(cmp::Ordering::Less);
+                    return cmp::Ordering::Less;
                 }
                 if left_list.len() > right_list.len() {
                     // right list ran out of items
-                    return Some(cmp::Ordering::Greater);
+                    return cmp::Ordering::Greater;
+                }
+                cmp::Ordering::Equal
+            }
+            _ => panic!("Shouldn't happen"),
+        }
+    }
+}
+
+impl PartialOrd for PacketData {
+    fn partial_cmp(&self, other: &PacketData) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// An integer compares equal to a list holding only that integer (or a list
+// holding only that list, and so on), otherwise lists must match item by item.
+impl PartialEq for PacketData {
+    fn eq(&self, other: &PacketData) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for PacketData {}
+
+impl Hash for PacketData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some(n) = self.as_integer() {
+            state.write_u8(0);
+            n.hash(state);
+        } else if let PacketData::List(list) = self {
+            state.write_u8(1);
+            state.write_usize(list.len());
+            list.iter().for_each(|p| p.hash(state));
+        }
+    }
+}
+
+fn trace_cmp(
+    left: &PacketData,
+    right: &PacketData,
+    depth: usize,
+    lines: &mut Vec<String>,
+) -> cmp::Ordering {
+    let indent = "  ".repeat(depth);
+    let inner = "  ".repeat(depth + 1);
+    lines.push(format!("{}- Compare {} vs {}", indent, left, right));
+    match (left, right) {
+        (PacketData::Integer(n), PacketData::Integer(m)) => {
+            let comparison = n.cmp(m);
+            match comparison {
+                cmp::Ordering::Less => lines.push(format!(
+                    "{}- Left side is smaller, so inputs are in the right order",
+                    inner
+                )),
+                cmp::Ordering::Greater => lines.push(format!(
+                    "{}- Right side is smaller, so inputs are not in the right order",
+                    inner
+                )),
+                cmp::Ordering::Equal => (),
+            }
+            comparison
+        }
+        (PacketData::Integer(_), PacketData::List(_)) => {
+            let converted = left.as_list();
+            lines.push(format!(
+                "{}- Mixed types; convert left to {} and retry comparison",
+                inner, converted
+            ));
+            trace_cmp(&converted, right, depth + 1, lines)
+        }
+        (PacketData::List(_), PacketData::Integer(_)) => {
+            let converted = right.as_list();
+            lines.push(format!(
+                "{}- Mixed types; convert right to {} and retry comparison",
+                inner, converted
+            ));
+            trace_cmp(left, &converted, depth + 1, lines)
+        }
+        (PacketData::List(left_list), PacketData::List(right_list)) => {
+            for (l, r) in left_list.iter().zip(right_list.iter()) {
+                let comparison = trace_cmp(l, r, depth + 1, lines);
+                if comparison != cmp::Ordering::Equal {
+                    return comparison;
+                }
+            }
+            let comparison = left_list.len().cmp(&right_list.len());
+            match comparison {
+                cmp::Ordering::Less => lines.push(format!(
+                    "{}- Left side ran out of items, so inputs are in the right order",
+                    inner
+                )),
+                cmp::Ordering::Greater => lines.push(format!(
+                    "{}- Right side ran out of items, so inputs are not in the right order",
+                    inner
+                )),
+                cmp::Ordering::Equal => (),
+            }
+            comparison
+        }
+    }
+}
+
+/// Compares the packets like `cmp` does, along with every step followed to
+/// reach the result, written like the explanation in the puzzle.
+pub fn explain_order(left: &PacketData, right: &PacketData) -> (cmp::Ordering, String) {
+    let mut lines = Vec::new();
+    let comparison = trace_cmp(left, right, 0, &mut lines);
+    if comparison == cmp::Ordering::Equal {
+        lines.push("  - Both sides are equal, so the order can't be decided".to_string());
+    }
+    (comparison, lines.join("\n"))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    Syntax(String),
+    /// Something other than an array or an integer, with the JSON pointer
+    /// of where it was found.
+    Unsupported {
+        pointer: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Syntax(e) => write!(f, "invalid JSON: {}", e),
+            JsonError::Unsupported { pointer, found } => write!(
+                f,
+                "expected an array or an integer at '{}', found {}",
+                pointer, found
+            ),
+        }
+    }
+}
+
+fn from_json(value: &Value, pointer: &str) -> Result<PacketData, JsonError> {
+    let unsupported = |found: &str| JsonError::Unsupported {
+        pointer: pointer.to_string(),
+        found: found.to_string(),
+    };
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| from_json(v, &format!("{}/{}", pointer, i)))
+            .collect::<Result<Vec<PacketData>, JsonError>>()
+            .map(PacketData::List),
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => Ok(PacketData::Integer(n)),
+            None => Err(unsupported(&format!("number {}", n))),
+        },
+        Value::Null => Err(unsupported("null")),
+        Value::Bool(_) => Err(unsupported("boolean")),
+        Value::String(_) => Err(unsupported("string")),
+        Value::Object(_) => Err(unsupported("object")),
+    }
+}
+
+impl TryFrom<&Value> for PacketData {
+    type Error = JsonError;
+
+    fn try_from(value: &Value) -> Result<PacketData, JsonError> {
+        from_json(value, "")
+    }
+}
+
+impl From<&PacketData> for Value {
+    fn from(packet: &PacketData) -> Value {
+        match packet {
+            PacketData::Integer(n) => Value::from(*n),
+            PacketData::List(items) => Value::Array(items.iter().map(Value::from).collect()),
+        }
+    }
+}
+
+impl PacketData {
+    pub fn from_json(input: &str) -> Result<PacketData, JsonError> {
+        let value =
+            serde_json::from_str::<Value>(input).map_err(|e| JsonError::Syntax(e.to_string()))?;
+        PacketData::try_from(&value)
+    }
+}
+
+/// Orders two JSON documents with the distress signal rules.
+pub fn compare_json(left: &str, right: &str) -> Result<cmp::Ordering, JsonError> {
+    Ok(PacketData::from_json(left)?.cmp(&PacketData::from_json(right)?))
+}
+
+/// Compares two packets straight from their text, without building them.
+pub fn compare_packets(left: &str, right: &str) -> Result<cmp::Ordering, ParseError> {
+    let mut left = Tokenizer::new(left);
+    let mut right = Tokenizer::new(right);
+    // tokens still to be read from an integer promoted to a list
+    let mut left_pending = Vec::new();
+    let mut right_pending = Vec::new();
+    loop {
+        let a = match left_pending.pop() {
+            Some(token) => Some(token),
+            None => left.next().transpose()?,
+        };
+        let b = match right_pending.pop() {
+            Some(token) => Some(token),
+            None => right.next().transpose()?,
+        };
+        match (a, b) {
+            (None, None) => return Ok(cmp::Ordering::Equal),
+            (Some(Token::Open), Some(Token::Open)) | (Some(Token::Close), Some(Token::Close)) => (),
+            (Some(Token::Integer(n)), Some(Token::Integer(m))) => {
+                if n != m {
+                    return Ok(n.cmp(&m));
                 }
-                Some(cmp::Ordering::Equal)
             }
+            // left list ran out of items
+            (Some(Token::Close), _) => return Ok(cmp::Ordering::Less),
+            // right list ran out of items
+            (_, Some(Token::Close)) => return Ok(cmp::Ordering::Greater),
+            (Some(Token::Integer(n)), Some(Token::Open)) => {
+                left_pending.push(Token::Close);
+                left_pending.push(Token::Integer(n));
+            }
+            (Some(Token::Open), Some(Token::Integer(m))) => {
+                right_pending.push(Token::Close);
+                right_pending.push(Token::Integer(m));
+            }
+            // both sides are single values, so they end at the same time
+            // unless one of them failed to parse, which was reported above
             _ => panic!("Shouldn't happen"),
         }
     }
 }
 
+/// Sorts the packets in `reader` (one per line, blank lines skipped) keeping
+/// them as text. Errors carry the line number alongside the position.
+pub fn sort_packets<R: BufRead>(reader: R) -> Result<Vec<String>, (usize, ParseError)> {
+    let mut packets = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| (number + 1, ParseError::new(0, &e.to_string())))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        for token in Tokenizer::new(&line) {
+            token.map_err(|e| (number + 1, e))?;
+        }
+        packets.push(line);
+    }
+    packets.sort_by(|a, b| compare_packets(a, b).unwrap());
+    Ok(packets)
+}
+
 #[aoc_generator(day13)]
 pub fn parse_input(input: &str) -> Vec<(PacketData, PacketData)> {
     input
@@ -124,11 +527,10 @@ pub fn parse_input(input: &str) -> Vec<(PacketData, PacketData)> {
 fn sum_packets_in_order(input: &[(PacketData, PacketData)]) -> u64 {
     input
         .iter()
-        .map(|pair| pair.0.partial_cmp(&pair.1))
-        .into_iter()
+        .map(|pair| pair.0.cmp(&pair.1))
         .enumerate()
         .map(|(i, ordering)| match ordering {
-            Some(cmp::Ordering::Less) => i + 1,
+            cmp::Ordering::Less => i + 1,
             _ => 0,
         })
         .sum::<usize>() as u64
@@ -136,21 +538,21 @@ fn sum_packets_in_order(input: &[(PacketData, PacketData)]) -> u64 {
 
 #[aoc(day13, part2)]
 fn locate_decoder_key(input: &[(PacketData, PacketData)]) -> u64 {
-    let mut input_packets = input
-        .iter()
-        .map(|p| vec![p.0.clone(), p.1.clone()])
-        .into_iter()
-        .flatten()
-        .collect::<Vec<PacketData>>();
     let divider1 = PacketData::from("[[2]]");
     let divider2 = PacketData::from("[[6]]");
-    input_packets.push(divider1.clone());
-    input_packets.push(divider2.clone());
-
-    input_packets.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-    let idx1 = input_packets.iter().position(|p| *p == divider1).unwrap() + 1;
-    let idx2 = input_packets.iter().position(|p| *p == divider2).unwrap() + 1;
+    // packets comparing equal to a divider (like [2]) could end up on either
+    // side of it when sorting, so rank the dividers by counting what goes
+    // strictly before them
+    let before = |divider: &PacketData| {
+        input
+            .iter()
+            .flat_map(|p| [&p.0, &p.1])
+            .filter(|p| *p < divider)
+            .count()
+    };
+    let idx1 = before(&divider1) + 1;
+    // the first divider also goes before the second one
+    let idx2 = before(&divider2) + 2;
 
     (idx1 * idx2) as u64
 }
@@ -217,4 +619,187 @@ mod tests {
         let input = parse_input(DAY13_EXAMPLE);
         assert_eq!(locate_decoder_key(&input), 140);
     }
+
+    #[test]
+    fn test_day13_decoder_key_equal_packets() {
+        // [2] and [[[6]]] compare equal to the dividers, which keep their
+        // place in front of them
+        let mut input = parse_input(DAY13_EXAMPLE);
+        input.push((PacketData::from("[2]"), PacketData::from("[[[6]]]")));
+        assert_eq!(locate_decoder_key(&input), 10 * 15);
+    }
+
+    #[test]
+    fn test_day13_parse_errors() {
+        let error = |input: &str| input.parse::<PacketData>().unwrap_err();
+        assert_eq!(error("[1,2"), ParseError::new(4, "unclosed list"));
+        assert_eq!(error("[1,,2]"), ParseError::new(3, "expected a value"));
+        assert_eq!(error("[1,]"), ParseError::new(3, "expected a value"));
+        assert_eq!(error("[1 2]"), ParseError::new(3, "expected ',' or ']'"));
+        assert_eq!(
+            error("[1],[2]"),
+            ParseError::new(3, "unexpected trailing characters")
+        );
+        assert_eq!(error("[a]"), ParseError::new(1, "unexpected character"));
+        assert_eq!(error(""), ParseError::new(0, "expected a value"));
+        assert_eq!(
+            error("[99999999999999999999]"),
+            ParseError::new(1, "integer too large")
+        );
+    }
+
+    #[test]
+    fn test_day13_round_trip() {
+        for line in DAY13_EXAMPLE.lines().filter(|l| !l.is_empty()) {
+            let packet = line.parse::<PacketData>().unwrap();
+            assert_eq!(packet.to_string(), line);
+        }
+        let packet = "[ [1], [2, 3] ]".parse::<PacketData>().unwrap();
+        assert_eq!(packet.to_string(), "[[1],[2,3]]");
+
+        let negative = PacketData::from_json("[-3,[0,-12]]").unwrap();
+        assert_eq!(negative.to_string(), "[-3,[0,-12]]");
+        assert_eq!(
+            negative
+                .to_string()
+                .parse::<PacketData>()
+                .unwrap()
+                .to_string(),
+            "[-3,[0,-12]]"
+        );
+        assert_eq!(
+            "[-]".parse::<PacketData>(),
+            Err(ParseError::new(2, "expected a digit"))
+        );
+        assert_eq!(
+            "[1-2]".parse::<PacketData>(),
+            Err(ParseError::new(2, "expected ',' or ']'"))
+        );
+    }
+
+    #[test]
+    fn test_day13_eq_hash() {
+        use std::collections::HashSet;
+        let packets = ["[1]", "1", "[[1]]", "[1,1]", "[[1,1]]"]
+            .iter()
+            .map(|s| PacketData::from(s))
+            .collect::<HashSet<PacketData>>();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(PacketData::from("[[1]]"), PacketData::from("1"));
+        assert_ne!(PacketData::from("[[1,1]]"), PacketData::from("[1,1]"));
+        assert_ne!(PacketData::from("[]"), PacketData::from("[[]]"));
+    }
+
+    #[test]
+    fn test_day13_compare_text() {
+        let lines = DAY13_EXAMPLE.lines().collect::<Vec<&str>>();
+        for pair in lines.chunks(3) {
+            let expected = PacketData::from(pair[0]).cmp(&PacketData::from(pair[1]));
+            assert_eq!(compare_packets(pair[0], pair[1]), Ok(expected));
+        }
+        assert!(compare_packets("[1]", "[1").is_err());
+    }
+
+    #[test]
+    fn test_day13_sort_packets() {
+        let input = format!("{}\n[[2]]\n[[6]]", DAY13_EXAMPLE);
+        let sorted = sort_packets(input.as_bytes()).unwrap();
+        assert_eq!(sorted.len(), 18);
+        assert_eq!(sorted[0], "[]");
+        assert_eq!(sorted.iter().position(|p| p == "[[2]]"), Some(9));
+        assert_eq!(sorted.iter().position(|p| p == "[[6]]"), Some(13));
+        assert_eq!(
+            sort_packets("[1]\n\n[2,]".as_bytes()),
+            Err((3, ParseError::new(3, "expected a value")))
+        );
+    }
+
+    #[test]
+    fn test_day13_explain_order() {
+        let input = parse_input(DAY13_EXAMPLE);
+        let (ordering, explanation) = explain_order(&input[1].0, &input[1].1);
+        assert_eq!(ordering, cmp::Ordering::Less);
+        assert_eq!(
+            explanation,
+            "- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order"
+        );
+
+        let (ordering, explanation) = explain_order(&input[2].0, &input[2].1);
+        assert_eq!(ordering, cmp::Ordering::Greater);
+        assert_eq!(
+            explanation,
+            "- Compare [9] vs [[8,7,6]]
+  - Compare 9 vs [8,7,6]
+    - Mixed types; convert left to [9] and retry comparison
+    - Compare [9] vs [8,7,6]
+      - Compare 9 vs 8
+        - Right side is smaller, so inputs are not in the right order"
+        );
+
+        let (_, explanation) = explain_order(&input[3].0, &input[3].1);
+        assert_eq!(
+            explanation,
+            "- Compare [[4,4],4,4] vs [[4,4],4,4,4]
+  - Compare [4,4] vs [4,4]
+    - Compare 4 vs 4
+    - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Compare 4 vs 4
+  - Left side ran out of items, so inputs are in the right order"
+        );
+
+        let (_, explanation) = explain_order(&input[6].0, &input[6].1);
+        assert_eq!(
+            explanation,
+            "- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order"
+        );
+
+        for (left, right) in input.iter() {
+            assert_eq!(explain_order(left, right).0, left.cmp(right));
+        }
+    }
+
+    #[test]
+    fn test_day13_json() {
+        let input = parse_input(DAY13_EXAMPLE);
+        for (left, right) in input.iter() {
+            let value = Value::from(left);
+            assert_eq!(value.to_string(), left.to_string());
+            let packet = PacketData::try_from(&value).unwrap();
+            assert_eq!(packet.to_string(), left.to_string());
+            assert_eq!(
+                compare_json(&left.to_string(), &right.to_string()),
+                Ok(left.cmp(right))
+            );
+        }
+
+        assert_eq!(
+            compare_json("[1, [2, -3]]", " [1,[2,4]] "),
+            Ok(cmp::Ordering::Less)
+        );
+        assert_eq!(
+            PacketData::from_json("[1,[2,{\"a\":1}]]"),
+            Err(JsonError::Unsupported {
+                pointer: "/1/1".to_string(),
+                found: "object".to_string(),
+            })
+        );
+        assert_eq!(
+            PacketData::from_json("[1.5]").unwrap_err().to_string(),
+            "expected an array or an integer at '/0', found number 1.5"
+        );
+        assert!(matches!(
+            compare_json("[1", "[1]"),
+            Err(JsonError::Syntax(_))
+        ));
+    }
 }
@@ -1,7 +1,16 @@
 use aoc_runner_derive::aoc;
 use aoc_runner_derive::aoc_generator;
 use itertools::Itertools;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
 
 type Cube = (i32, i32, i32);
 
@@ -18,99 +27,548 @@ pub fn parse_input(input: &str) -> BTreeSet<Cube> {
         .collect::<BTreeSet<Cube>>()
 }
 
+const NEIGHBORS: [Cube; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// Largest bounding box, in voxels, that `Voxels` agrees to allocate.
+pub const MAX_VOXELS: usize = 1 << 28;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VoxelsError {
+    /// Cube on the edge of the `i32` range, leaving no room for the margin.
+    OutOfRange(Cube),
+    /// Bounding box with more than `MAX_VOXELS` voxels.
+    TooLarge { min: Cube, max: Cube },
+}
+
+impl fmt::Display for VoxelsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxelsError::OutOfRange(c) => write!(f, "Cube {:?} out of range", c),
+            VoxelsError::TooLarge { min, max } => {
+                write!(f, "Bounding box {:?}-{:?} too large", min, max)
+            }
+        }
+    }
+}
+
+/// Dense bitmap of voxels covering the bounding box of a droplet with one
+/// voxel of margin on every side, so the outside is always connected.
+#[derive(Clone)]
+pub struct Voxels {
+    min: Cube,
+    size: (usize, usize, usize),
+    bits: Vec<u64>,
+}
+
+impl Voxels {
+    fn empty(min: Cube, max: Cube) -> Result<Voxels, VoxelsError> {
+        let size = (
+            (max.0 as i64 - min.0 as i64 + 1) as usize,
+            (max.1 as i64 - min.1 as i64 + 1) as usize,
+            (max.2 as i64 - min.2 as i64 + 1) as usize,
+        );
+        let len = size
+            .0
+            .checked_mul(size.1)
+            .and_then(|len| len.checked_mul(size.2))
+            .filter(|len| *len <= MAX_VOXELS)
+            .ok_or(VoxelsError::TooLarge { min, max })?;
+        Ok(Voxels {
+            min,
+            size,
+            bits: vec![0; len.div_ceil(64)],
+        })
+    }
+
+    /// Fails if the bounding box would take more than `MAX_VOXELS` or a cube
+    /// sits on the edge of the `i32` range.
+    pub fn from_cubes<'a, I: IntoIterator<Item = &'a Cube>>(
+        cubes: I,
+    ) -> Result<Voxels, VoxelsError> {
+        let cubes = cubes.into_iter().collect::<Vec<&Cube>>();
+        let mut min = (i32::MAX, i32::MAX, i32::MAX);
+        let mut max = (i32::MIN, i32::MIN, i32::MIN);
+        for c in cubes.iter() {
+            let out_of_range = || VoxelsError::OutOfRange(**c);
+            let lower = (c.0.checked_sub(1), c.1.checked_sub(1), c.2.checked_sub(1));
+            let upper = (c.0.checked_add(1), c.1.checked_add(1), c.2.checked_add(1));
+            let (Some(x0), Some(y0), Some(z0)) = lower else {
+                return Err(out_of_range());
+            };
+            let (Some(x1), Some(y1), Some(z1)) = upper else {
+                return Err(out_of_range());
+            };
+            min = (min.0.min(x0), min.1.min(y0), min.2.min(z0));
+            max = (max.0.max(x1), max.1.max(y1), max.2.max(z1));
+        }
+        if cubes.is_empty() {
+            (min, max) = ((0, 0, 0), (0, 0, 0));
+        }
+        let mut voxels = Voxels::empty(min, max)?;
+        for c in cubes {
+            voxels.insert(c);
+        }
+        Ok(voxels)
+    }
+
+    fn same_bounds(&self) -> Voxels {
+        Voxels {
+            min: self.min,
+            size: self.size,
+            bits: vec![0; self.bits.len()],
+        }
+    }
+
+    /// Smallest and largest corner of the bitmap, margin included.
+    pub fn bounds(&self) -> (Cube, Cube) {
+        (
+            self.min,
+            (
+                self.min.0 + self.size.0 as i32 - 1,
+                self.min.1 + self.size.1 as i32 - 1,
+                self.min.2 + self.size.2 as i32 - 1,
+            ),
+        )
+    }
+
+    fn index(&self, c: &Cube) -> Option<usize> {
+        let (x, y, z) = (c.0 - self.min.0, c.1 - self.min.1, c.2 - self.min.2);
+        if x < 0 || y < 0 || z < 0 {
+            return None;
+        }
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        if x >= self.size.0 || y >= self.size.1 || z >= self.size.2 {
+            return None;
+        }
+        Some((z * self.size.1 + y) * self.size.0 + x)
+    }
+
+    fn cube_at(&self, index: usize) -> Cube {
+        let x = index % self.size.0;
+        let y = (index / self.size.0) % self.size.1;
+        let z = index / (self.size.0 * self.size.1);
+        (
+            self.min.0 + x as i32,
+            self.min.1 + y as i32,
+            self.min.2 + z as i32,
+        )
+    }
+
+    fn get(&self, index: usize) -> bool {
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    fn set(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn contains(&self, c: &Cube) -> bool {
+        self.index(c).is_some_and(|i| self.get(i))
+    }
+
+    /// Panics if the cube is outside the bounds of the bitmap.
+    pub fn insert(&mut self, c: &Cube) {
+        let index = self
+            .index(c)
+            .unwrap_or_else(|| panic!("Cube {:?} out of bounds", c));
+        self.set(index);
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|w| *w == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Cube> + '_ {
+        let len = self.size.0 * self.size.1 * self.size.2;
+        (0..len).filter(|i| self.get(*i)).map(|i| self.cube_at(i))
+    }
+
+    /// Marks in `filled` every voxel reachable from `from` without crossing
+    /// `self` or an already filled voxel, calling `visit` on each of them.
+    fn flood_fill<F: FnMut(usize)>(&self, from: usize, filled: &mut Voxels, mut visit: F) {
+        let mut open_set = vec![from];
+        filled.set(from);
+        while let Some(i) = open_set.pop() {
+            visit(i);
+            let c = self.cube_at(i);
+            for d in NEIGHBORS.iter() {
+                if let Some(n) = self.index(&(c.0 + d.0, c.1 + d.1, c.2 + d.2)) {
+                    if !self.get(n) && !filled.get(n) {
+                        filled.set(n);
+                        open_set.push(n);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every empty voxel connected to the outside of the droplet.
+    pub fn exterior(&self) -> Voxels {
+        let mut exterior = self.same_bounds();
+        self.flood_fill(0, &mut exterior, |_| ());
+        exterior
+    }
+
+    /// Enclosed air pockets, as the list of voxels in each of them (so their
+    /// volume is the length of the list).
+    pub fn air_pockets(&self) -> Vec<Vec<Cube>> {
+        let mut visited = self.exterior();
+        let mut pockets = Vec::new();
+        let len = self.size.0 * self.size.1 * self.size.2;
+        for i in 0..len {
+            if self.get(i) || visited.get(i) {
+                continue;
+            }
+            let mut pocket = Vec::new();
+            self.flood_fill(i, &mut visited, |n| pocket.push(self.cube_at(n)));
+            pocket.sort();
+            pockets.push(pocket);
+        }
+        pockets
+    }
+
+    fn count_faces<F: Fn(&Cube) -> bool>(&self, exposed: F) -> u64 {
+        self.iter()
+            .map(|c| {
+                NEIGHBORS
+                    .iter()
+                    .map(|d| (c.0 + d.0, c.1 + d.1, c.2 + d.2))
+                    .filter(|n| exposed(n))
+                    .count() as u64
+            })
+            .sum()
+    }
+
+    pub fn surface_area(&self) -> u64 {
+        self.count_faces(|n| !self.contains(n))
+    }
+
+    pub fn exterior_surface_area(&self) -> u64 {
+        let exterior = self.exterior();
+        self.count_faces(|n| exterior.contains(n))
+    }
+
+    /// Groups the cubes into clusters that touch each other, either only
+    /// through faces or also through edges and corners.
+    pub fn components(&self, connectivity: Connectivity) -> Vec<Component> {
+        let offsets = connectivity.offsets();
+        let mut visited = self.same_bounds();
+        let mut components = Vec::new();
+        let len = self.size.0 * self.size.1 * self.size.2;
+        for i in 0..len {
+            if !self.get(i) || visited.get(i) {
+                continue;
+            }
+            let mut cubes = Vec::new();
+            let mut open_set = vec![i];
+            visited.set(i);
+            while let Some(i) = open_set.pop() {
+                let c = self.cube_at(i);
+                cubes.push(c);
+                for d in offsets.iter() {
+                    if let Some(n) = self.index(&(c.0 + d.0, c.1 + d.1, c.2 + d.2)) {
+                        if self.get(n) && !visited.get(n) {
+                            visited.set(n);
+                            open_set.push(n);
+                        }
+                    }
+                }
+            }
+            components.push(Component::new(cubes));
+        }
+        components
+    }
+
+    /// Mesh with a quad for every exposed face, or only for the ones facing
+    /// the outside of the droplet when `exterior_only` is set.
+    pub fn mesh(&self, exterior_only: bool) -> Mesh {
+        let exterior = if exterior_only {
+            Some(self.exterior())
+        } else {
+            None
+        };
+        let mut quads = Vec::new();
+        for c in self.iter() {
+            for d in NEIGHBORS.iter() {
+                let n = (c.0 + d.0, c.1 + d.1, c.2 + d.2);
+                let exposed = match &exterior {
+                    Some(exterior) => exterior.contains(&n),
+                    None => !self.contains(&n),
+                };
+                if exposed {
+                    quads.push(Quad::new(&c, d));
+                }
+            }
+        }
+        Mesh { quads }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Cubes sharing a face (6 neighbors).
+    Faces,
+    /// Cubes sharing a face, an edge or a corner (26 neighbors).
+    Corners,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> Vec<Cube> {
+        match self {
+            Connectivity::Faces => NEIGHBORS.to_vec(),
+            Connectivity::Corners => itertools::iproduct!(-1..=1, -1..=1, -1..=1)
+                .filter(|d| *d != (0, 0, 0))
+                .collect(),
+        }
+    }
+}
+
+/// Cluster of connected cubes of a droplet.
+pub struct Component {
+    pub cubes: Vec<Cube>,
+    pub volume: usize,
+    pub surface_area: u64,
+    pub exterior_surface_area: u64,
+    /// Smallest and largest cube of the component.
+    pub bounds: (Cube, Cube),
+    pub cavities: usize,
+}
+
+impl Component {
+    fn new(mut cubes: Vec<Cube>) -> Component {
+        cubes.sort();
+        // a component never has a bigger bounding box than its droplet
+        let voxels = Voxels::from_cubes(&cubes).unwrap();
+        let (min, max) = voxels.bounds();
+        Component {
+            volume: cubes.len(),
+            surface_area: voxels.surface_area(),
+            exterior_surface_area: voxels.exterior_surface_area(),
+            bounds: (
+                (min.0 + 1, min.1 + 1, min.2 + 1),
+                (max.0 - 1, max.1 - 1, max.2 - 1),
+            ),
+            cavities: voxels.air_pockets().len(),
+            cubes,
+        }
+    }
+}
+
+/// Face of a unit cube, with its corners in counter-clockwise order when
+/// looking at it from the outside.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quad {
+    pub corners: [Cube; 4],
+    pub normal: Cube,
+}
+
+impl Quad {
+    fn new(cube: &Cube, normal: &Cube) -> Quad {
+        let origin = [cube.0, cube.1, cube.2];
+        let dir = [normal.0, normal.1, normal.2];
+        let axis = dir.iter().position(|v| *v != 0).unwrap();
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let offsets = if dir[axis] > 0 {
+            [(0, 0), (1, 0), (1, 1), (0, 1)]
+        } else {
+            [(0, 0), (0, 1), (1, 1), (1, 0)]
+        };
+        let corners = offsets.map(|(du, dv)| {
+            let mut corner = origin;
+            if dir[axis] > 0 {
+                corner[axis] += 1;
+            }
+            corner[u] += du;
+            corner[v] += dv;
+            (corner[0], corner[1], corner[2])
+        });
+        Quad {
+            corners,
+            normal: *normal,
+        }
+    }
+
+    pub fn triangles(&self) -> [[Cube; 3]; 2] {
+        let c = self.corners;
+        [[c[0], c[1], c[2]], [c[0], c[2], c[3]]]
+    }
+}
+
+/// Surface of a droplet made of the exposed faces of its cubes.
+pub struct Mesh {
+    pub quads: Vec<Quad>,
+}
+
+impl Mesh {
+    pub fn write_obj<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut vertices = BTreeMap::new();
+        for corner in self.quads.iter().flat_map(|q| q.corners.iter()) {
+            let next = vertices.len() + 1;
+            vertices.entry(*corner).or_insert(next);
+        }
+        let mut sorted = vertices.iter().collect::<Vec<(&Cube, &usize)>>();
+        sorted.sort_by_key(|(_, i)| **i);
+        for (v, _) in sorted {
+            writeln!(writer, "v {} {} {}", v.0, v.1, v.2)?;
+        }
+        for n in NEIGHBORS.iter() {
+            writeln!(writer, "vn {} {} {}", n.0, n.1, n.2)?;
+        }
+        for q in self.quads.iter() {
+            let normal = NEIGHBORS.iter().position(|n| *n == q.normal).unwrap() + 1;
+            for t in q.triangles() {
+                writeln!(
+                    writer,
+                    "f {}//{} {}//{} {}//{}",
+                    vertices[&t[0]], normal, vertices[&t[1]], normal, vertices[&t[2]], normal
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_stl<W: Write>(&self, mut writer: W, name: &str) -> io::Result<()> {
+        writeln!(writer, "solid {}", name)?;
+        for q in self.quads.iter() {
+            for t in q.triangles() {
+                let n = q.normal;
+                writeln!(writer, "  facet normal {} {} {}", n.0, n.1, n.2)?;
+                writeln!(writer, "    outer loop")?;
+                for v in t {
+                    writeln!(writer, "      vertex {} {} {}", v.0, v.1, v.2)?;
+                }
+                writeln!(writer, "    endloop")?;
+                writeln!(writer, "  endfacet")?;
+            }
+        }
+        writeln!(writer, "endsolid {}", name)
+    }
+
+    pub fn save_obj(&self, path: &Path) -> io::Result<()> {
+        self.write_obj(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn save_stl(&self, path: &Path) -> io::Result<()> {
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("droplet");
+        self.write_stl(BufWriter::new(File::create(path)?), name)
+    }
+}
+
 #[aoc(day18, part1)]
 pub fn find_surface_area(cubes: &BTreeSet<Cube>) -> u64 {
-    let mut sum = 0;
-    for c in cubes.iter() {
-        sum += [
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 1, 0),
-            (0, -1, 0),
-            (0, 0, 1),
-            (0, 0, -1),
-        ]
-        .into_iter()
-        .map(|d| (c.0 + d.0, c.1 + d.1, c.2 + d.2))
-        .filter(|c| !cubes.contains(c))
-        .count();
-    }
-    sum as u64
-}
-
-fn is_open_water(cube: &Cube, min: &Cube, max: &Cube) -> bool {
-    cube.0 == min.0
-        || cube.0 == max.0
-        || cube.1 == min.1
-        || cube.1 == max.1
-        || cube.2 == min.2
-        || cube.2 == max.2
-}
-
-fn in_pocket(set: &BTreeSet<Cube>, cube: &Cube, min: &Cube, max: &Cube) -> bool {
-    let mut closed_set = BTreeSet::new();
-    let mut open_set = Vec::new();
-    open_set.push(*cube);
-    while !open_set.is_empty() {
-        let c = open_set.pop().unwrap();
-        closed_set.insert(c);
-
-        if is_open_water(&c, min, max) {
-            return false;
-        }
-
-        [
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 1, 0),
-            (0, -1, 0),
-            (0, 0, 1),
-            (0, 0, -1),
-        ]
+    // counting on the set works however sparse the cubes are
+    let neighbor = |c: &Cube, d: Cube| {
+        Some((
+            c.0.checked_add(d.0)?,
+            c.1.checked_add(d.1)?,
+            c.2.checked_add(d.2)?,
+        ))
+    };
+    cubes
+        .iter()
+        .flat_map(|c| NEIGHBORS.map(|d| neighbor(c, d)))
+        .filter(|n| !n.is_some_and(|n| cubes.contains(&n)))
+        .count() as u64
+}
+
+type Cell = (i64, i64, i64);
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn join(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find_root(parent, a), find_root(parent, b));
+    parent[a] = b;
+}
+
+/// Exterior surface area for droplets too spread out for `Voxels`. Only the
+/// air touching a cube (even through an edge or a corner) is kept, which
+/// splits the outside into separate shells around each cluster. The air
+/// right before a cube along -x gets linked to whatever the ray in that
+/// direction hits first, and it's the outside if the ray hits nothing.
+fn sparse_exterior_surface_area(cubes: &BTreeSet<Cube>) -> u64 {
+    let solid = cubes
         .iter()
-        .map(|d| (c.0 + d.0, c.1 + d.1, c.2 + d.2))
-        .for_each(|c| {
-            if !closed_set.contains(&c) && !set.contains(&c) {
-                open_set.push(c);
+        .map(|c| (c.0 as i64, c.1 as i64, c.2 as i64))
+        .collect::<HashSet<Cell>>();
+    let mut cells = Vec::new();
+    let mut index = HashMap::new();
+    for c in solid.iter() {
+        for d in itertools::iproduct!(-1..=1, -1..=1, -1..=1) {
+            let n = (c.0 + d.0, c.1 + d.1, c.2 + d.2);
+            if !solid.contains(&n) && !index.contains_key(&n) {
+                index.insert(n, cells.len());
+                cells.push(n);
             }
-        });
+        }
+    }
+
+    let mut parent = (0..cells.len()).collect::<Vec<usize>>();
+    for (i, c) in cells.iter().enumerate() {
+        for d in NEIGHBORS.iter() {
+            let n = (c.0 + d.0 as i64, c.1 + d.1 as i64, c.2 + d.2 as i64);
+            if let Some(j) = index.get(&n) {
+                join(&mut parent, i, *j);
+            }
+        }
     }
 
-    true
+    // cubes sorted by row, so the first one hit by a ray is a range lookup
+    let rows = solid
+        .iter()
+        .map(|c| (c.1, c.2, c.0))
+        .collect::<BTreeSet<Cell>>();
+    let mut outside = Vec::new();
+    for (i, c) in cells.iter().enumerate() {
+        if !solid.contains(&(c.0 + 1, c.1, c.2)) {
+            continue;
+        }
+        match rows
+            .range((c.1, c.2, i64::MIN)..(c.1, c.2, c.0))
+            .next_back()
+        {
+            Some((_, _, x)) => join(&mut parent, i, index[&(x + 1, c.1, c.2)]),
+            None => outside.push(i),
+        }
+    }
+    let outside = outside
+        .into_iter()
+        .map(|i| find_root(&mut parent, i))
+        .collect::<HashSet<usize>>();
+
+    solid
+        .iter()
+        .flat_map(|c| NEIGHBORS.map(|d| (c.0 + d.0 as i64, c.1 + d.1 as i64, c.2 + d.2 as i64)))
+        .filter_map(|n| index.get(&n))
+        .filter(|i| outside.contains(&find_root(&mut parent, **i)))
+        .count() as u64
 }
 
 #[aoc(day18, part2)]
 pub fn find_surface_area_without_pockets(cubes: &BTreeSet<Cube>) -> u64 {
-    let mut sum = 0;
-    let mut min = (i32::MAX, i32::MAX, i32::MAX);
-    let mut max = (i32::MIN, i32::MIN, i32::MIN);
-    cubes.iter().for_each(|c| {
-        min = (
-            std::cmp::min(min.0, c.0 - 1),
-            std::cmp::min(min.1, c.1 - 1),
-            std::cmp::min(min.2, c.2 - 1),
-        );
-        max = (
-            std::cmp::max(max.0, c.0 + 1),
-            std::cmp::max(max.1, c.1 + 1),
-            std::cmp::max(max.2, c.2 + 1),
-        );
-    });
-    for c in cubes.iter() {
-        sum += [
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 1, 0),
-            (0, -1, 0),
-            (0, 0, 1),
-            (0, 0, -1),
-        ]
-        .into_iter()
-        .map(|d| (c.0 + d.0, c.1 + d.1, c.2 + d.2))
-        .filter(|c| !cubes.contains(c) && !in_pocket(cubes, c, &min, &max))
-        .count();
+    match Voxels::from_cubes(cubes) {
+        Ok(voxels) => voxels.exterior_surface_area(),
+        Err(_) => sparse_exterior_surface_area(cubes),
     }
-    sum as u64
 }
 
 #[cfg(test)]
@@ -137,9 +595,150 @@ mod tests {
         assert_eq!(find_surface_area(&input), 64);
     }
 
+    #[test]
+    fn test_day18_part1_sparse() {
+        let cubes = BTreeSet::from([
+            (0, 0, 0),
+            (100_000, 100_000, 100_000),
+            (100_001, 100_000, 100_000),
+        ]);
+        assert_eq!(find_surface_area(&cubes), 16);
+    }
+
     #[test]
     fn test_day18_part2() {
         let input = parse_input(DAY18_EXAMPLE);
         assert_eq!(find_surface_area_without_pockets(&input), 58);
+        assert_eq!(sparse_exterior_surface_area(&input), 58);
+    }
+
+    #[test]
+    fn test_day18_part2_sparse() {
+        let cubes = BTreeSet::from([
+            (0, 0, 0),
+            (100_000, 100_000, 100_000),
+            (100_001, 100_000, 100_000),
+        ]);
+        assert!(Voxels::from_cubes(&cubes).is_err());
+        assert_eq!(find_surface_area_without_pockets(&cubes), 16);
+
+        let edges = BTreeSet::from([(i32::MIN, 0, 0), (i32::MAX, 0, 0)]);
+        assert_eq!(
+            Voxels::from_cubes(&edges).err(),
+            Some(VoxelsError::OutOfRange((i32::MIN, 0, 0)))
+        );
+        assert_eq!(find_surface_area(&edges), 12);
+        assert_eq!(find_surface_area_without_pockets(&edges), 12);
+
+        // the example far inside a hollow cube, and another cube far away
+        let mut cubes = BTreeSet::new();
+        for (x, y, z) in itertools::iproduct!(0..20, 0..20, 0..20) {
+            if [x, y, z].iter().any(|v| *v == 0 || *v == 19) {
+                cubes.insert((x, y, z));
+            }
+        }
+        let dense = find_surface_area_without_pockets(&cubes);
+        cubes.extend(
+            parse_input(DAY18_EXAMPLE)
+                .iter()
+                .map(|c| (c.0 + 8, c.1 + 8, c.2 + 8)),
+        );
+        assert_eq!(sparse_exterior_surface_area(&cubes), dense);
+        cubes.insert((1_000_000, 0, 0));
+        assert_eq!(find_surface_area_without_pockets(&cubes), dense + 6);
+    }
+
+    #[test]
+    fn test_day18_air_pockets() {
+        let input = parse_input(DAY18_EXAMPLE);
+        let voxels = Voxels::from_cubes(&input).unwrap();
+        assert_eq!(voxels.len(), input.len());
+        assert_eq!(voxels.air_pockets(), vec![vec![(2, 2, 5)]]);
+    }
+
+    #[test]
+    fn test_day18_hollow_cube() {
+        // 20x20x20 shell with a 18x18x18 hole inside
+        let mut cubes = BTreeSet::new();
+        for (x, y, z) in itertools::iproduct!(0..20, 0..20, 0..20) {
+            if [x, y, z].iter().any(|v| *v == 0 || *v == 19) {
+                cubes.insert((x, y, z));
+            }
+        }
+        let voxels = Voxels::from_cubes(&cubes).unwrap();
+        assert_eq!(voxels.exterior_surface_area(), 6 * 20 * 20);
+        assert_eq!(voxels.surface_area(), 6 * 20 * 20 + 6 * 18 * 18);
+        let pockets = voxels.air_pockets();
+        assert_eq!(pockets.len(), 1);
+        assert_eq!(pockets[0].len(), 18 * 18 * 18);
+    }
+
+    #[test]
+    fn test_day18_mesh() {
+        let input = parse_input(DAY18_EXAMPLE);
+        let voxels = Voxels::from_cubes(&input).unwrap();
+        assert_eq!(voxels.mesh(false).quads.len(), 64);
+        assert_eq!(voxels.mesh(true).quads.len(), 58);
+
+        let single = Voxels::from_cubes(&[(0, 0, 0)]).unwrap().mesh(false);
+        let mut obj = Vec::new();
+        single.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 8);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 12);
+
+        let mut stl = Vec::new();
+        single.write_stl(&mut stl, "cube").unwrap();
+        let stl = String::from_utf8(stl).unwrap();
+        assert!(stl.starts_with("solid cube\n"));
+        assert!(stl.ends_with("endsolid cube\n"));
+        assert_eq!(stl.matches("facet normal").count(), 12);
+    }
+
+    #[test]
+    fn test_day18_quad_winding() {
+        // the cross product of the triangle edges must point along the normal
+        let single = Voxels::from_cubes(&[(0, 0, 0)]).unwrap().mesh(false);
+        for q in single.quads.iter() {
+            for [a, b, c] in q.triangles() {
+                let (u, v) = (
+                    (b.0 - a.0, b.1 - a.1, b.2 - a.2),
+                    (c.0 - a.0, c.1 - a.1, c.2 - a.2),
+                );
+                let cross = (
+                    u.1 * v.2 - u.2 * v.1,
+                    u.2 * v.0 - u.0 * v.2,
+                    u.0 * v.1 - u.1 * v.0,
+                );
+                assert_eq!(cross, q.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn test_day18_components() {
+        let input = parse_input(DAY18_EXAMPLE);
+        let voxels = Voxels::from_cubes(&input).unwrap();
+
+        // the cubes around the cavity at 2,2,5 only touch the rest by edges
+        assert_eq!(voxels.components(Connectivity::Faces).len(), 6);
+        let components = voxels.components(Connectivity::Corners);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].volume, 13);
+        assert_eq!(components[0].surface_area, 64);
+        assert_eq!(components[0].exterior_surface_area, 58);
+        assert_eq!(components[0].bounds, ((1, 1, 1), (3, 3, 6)));
+        assert_eq!(components[0].cavities, 1);
+
+        // two cubes touching only through an edge
+        let voxels = Voxels::from_cubes(&[(0, 0, 0), (1, 1, 0), (5, 5, 5)]).unwrap();
+        let faces = voxels.components(Connectivity::Faces);
+        assert_eq!(faces.len(), 3);
+        assert!(faces.iter().all(|c| c.volume == 1 && c.surface_area == 6));
+        let corners = voxels.components(Connectivity::Corners);
+        assert_eq!(corners.len(), 2);
+        assert_eq!(corners[0].cubes, vec![(0, 0, 0), (1, 1, 0)]);
+        assert_eq!(corners[0].surface_area, 12);
+        assert_eq!(corners[0].cavities, 0);
     }
 }
@@ -1,6 +1,9 @@
 use aoc_runner_derive::aoc;
 use aoc_runner_derive::aoc_generator;
+use num_rational::Ratio;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Monkey {
@@ -49,88 +52,456 @@ pub fn parse_input(input: &str) -> Monkeys {
     map
 }
 
-fn get_monkey(map: &Monkeys, key: &str) -> i64 {
-    let monkey = &map[key];
-    match monkey {
-        Monkey::Number(n) => *n,
-        Monkey::Add(k1, k2) => get_monkey(map, k1) + get_monkey(map, k2),
-        Monkey::Sub(k1, k2) => get_monkey(map, k1) - get_monkey(map, k2),
-        Monkey::Mul(k1, k2) => get_monkey(map, k1) * get_monkey(map, k2),
-        Monkey::Div(k1, k2) => get_monkey(map, k1) / get_monkey(map, k2),
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobError {
+    /// A monkey listens to another that doesn't exist (`referenced_by` is
+    /// `None` when the missing one is where we started).
+    Undefined {
+        name: String,
+        referenced_by: Option<String>,
+    },
+    /// Chain of monkeys listening to each other, starting and ending with
+    /// the same one.
+    Cycle(Vec<String>),
+    DivisionByZero(String),
+}
+
+/// Monkeys that `root` depends on (itself included), ordered so that every
+/// monkey comes after the ones it listens to.
+pub fn topological_order(map: &Monkeys, root: &str) -> Result<Vec<String>, JobError> {
+    if !map.contains_key(root) {
+        return Err(JobError::Undefined {
+            name: root.to_string(),
+            referenced_by: None,
+        });
+    }
+
+    let mut order = Vec::new();
+    let mut done = HashSet::new();
+    // monkeys being visited, along with how many of their branches have
+    // been pushed already
+    let mut path: Vec<(&str, usize)> = vec![(root, 0)];
+    let mut on_path = HashSet::from([root]);
+    while let Some((key, visited)) = path.last_mut() {
+        let monkey = &map[*key];
+        if monkey.as_op().is_none() || *visited == 2 {
+            done.insert(*key);
+            on_path.remove(*key);
+            order.push(key.to_string());
+            path.pop();
+            continue;
+        }
+        let (k1, k2) = monkey.branches();
+        let next = if *visited == 0 { k1 } else { k2 };
+        *visited += 1;
+        let key = *key;
+
+        if done.contains(next.as_str()) {
+            continue;
+        }
+        if !map.contains_key(next) {
+            return Err(JobError::Undefined {
+                name: next.to_string(),
+                referenced_by: Some(key.to_string()),
+            });
+        }
+        if on_path.contains(next.as_str()) {
+            let start = path.iter().position(|(k, _)| k == next).unwrap();
+            let mut chain = path[start..]
+                .iter()
+                .map(|(k, _)| k.to_string())
+                .collect::<Vec<String>>();
+            chain.push(next.to_string());
+            return Err(JobError::Cycle(chain));
+        }
+        on_path.insert(next);
+        path.push((next, 0));
     }
+    Ok(order)
+}
+
+/// Number yelled by the monkey `key`, evaluating its dependencies in
+/// topological order instead of recursing.
+pub fn evaluate(map: &Monkeys, key: &str) -> Result<i64, JobError> {
+    let mut values: HashMap<&str, i64> = HashMap::new();
+    let order = topological_order(map, key)?;
+    for name in order.iter() {
+        let monkey = &map[name];
+        let value = match monkey {
+            Monkey::Number(n) => *n,
+            _ => {
+                let (k1, k2) = monkey.branches();
+                let (a, b) = (values[k1.as_str()], values[k2.as_str()]);
+                match monkey {
+                    Monkey::Add(_, _) => a + b,
+                    Monkey::Sub(_, _) => a - b,
+                    Monkey::Mul(_, _) => a * b,
+                    Monkey::Div(_, _) if b == 0 => {
+                        return Err(JobError::DivisionByZero(name.to_string()))
+                    }
+                    Monkey::Div(_, _) => a / b,
+                    Monkey::Number(_) => unreachable!(),
+                }
+            }
+        };
+        values.insert(name, value);
+    }
+    Ok(values[key])
 }
 
 #[aoc(day21, part1)]
 pub fn get_root(input: &Monkeys) -> i64 {
-    get_monkey(input, "root")
+    evaluate(input, "root").unwrap()
+}
+
+pub type Rational = Ratio<i128>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Job of a monkey (and all the monkeys it depends on) as a formula, with
+/// one of the monkeys left as an unknown.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(Rational),
+    Unknown(String),
+    Op(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    Less,
+    Greater,
+}
+
+impl Comparison {
+    fn flip(self) -> Comparison {
+        match self {
+            Comparison::Equal => Comparison::Equal,
+            Comparison::Less => Comparison::Greater,
+            Comparison::Greater => Comparison::Less,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveError {
+    InvalidJobs(JobError),
+    NotAnOperation(String),
+    UnknownOnBothSides,
+    UnknownMissing,
+    NonLinear,
+    DivisionByZero,
+    /// The unknown cancels out, so either every value or none satisfies
+    /// the comparison.
+    NoUniqueSolution,
+}
+
+/// `coef * unknown + constant`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Linear {
+    pub coef: Rational,
+    pub constant: Rational,
+}
+
+impl Linear {
+    fn constant(value: Rational) -> Linear {
+        Linear {
+            coef: Rational::from_integer(0),
+            constant: value,
+        }
+    }
+
+    fn unknown() -> Linear {
+        Linear {
+            coef: Rational::from_integer(1),
+            constant: Rational::from_integer(0),
+        }
+    }
+
+    fn is_constant(&self) -> bool {
+        *self.coef.numer() == 0
+    }
+
+    fn combine(op: Op, a: &Linear, b: &Linear) -> Result<Linear, SolveError> {
+        match op {
+            Op::Add => Ok(Linear {
+                coef: a.coef + b.coef,
+                constant: a.constant + b.constant,
+            }),
+            Op::Sub => Ok(Linear {
+                coef: a.coef - b.coef,
+                constant: a.constant - b.constant,
+            }),
+            Op::Mul => {
+                if !a.is_constant() && !b.is_constant() {
+                    return Err(SolveError::NonLinear);
+                }
+                let (k, l) = if a.is_constant() { (a, b) } else { (b, a) };
+                Ok(Linear {
+                    coef: l.coef * k.constant,
+                    constant: l.constant * k.constant,
+                })
+            }
+            Op::Div => {
+                if !b.is_constant() {
+                    return Err(SolveError::NonLinear);
+                }
+                if *b.constant.numer() == 0 {
+                    return Err(SolveError::DivisionByZero);
+                }
+                Ok(Linear {
+                    coef: a.coef / b.constant,
+                    constant: a.constant / b.constant,
+                })
+            }
+        }
+    }
 }
 
-fn has_monkey(input: &Monkeys, root: &str, key: &str) -> bool {
-    if key == root {
-        return true;
+// linear form of `key`, and whether it depends on `unknown`. Monkeys are
+// reduced in topological order, remembering the result of each one, so deep
+// chains of jobs don't overflow the stack. Errors are kept per monkey and
+// only reported if `key` actually depends on them.
+fn linear_form(
+    map: &Monkeys,
+    key: &str,
+    unknown: &str,
+) -> Result<(Result<Linear, SolveError>, bool), JobError> {
+    let mut forms: HashMap<&str, (Result<Linear, SolveError>, bool)> = HashMap::new();
+    let order = topological_order(map, key)?;
+    for name in order.iter() {
+        let monkey = &map[name];
+        let form = if name == unknown {
+            (Ok(Linear::unknown()), true)
+        } else if let Monkey::Number(n) = monkey {
+            (
+                Ok(Linear::constant(Rational::from_integer(*n as i128))),
+                false,
+            )
+        } else {
+            let (k1, k2) = monkey.branches();
+            let ((a, a_unknown), (b, b_unknown)) = (&forms[k1.as_str()], &forms[k2.as_str()]);
+            let linear = match (a, b) {
+                (Ok(a), Ok(b)) => Linear::combine(monkey.as_op().unwrap(), a, b),
+                (Err(e), _) | (_, Err(e)) => Err(e.clone()),
+            };
+            (linear, *a_unknown || *b_unknown)
+        };
+        forms.insert(name, form);
     }
-    let monkey = &input[root];
-    match monkey {
-        Monkey::Number(_) => false,
-        Monkey::Add(k1, k2) | Monkey::Div(k1, k2) | Monkey::Sub(k1, k2) | Monkey::Mul(k1, k2) => {
-            has_monkey(input, k1, key) || has_monkey(input, k2, key)
+    Ok(forms.remove(key).unwrap())
+}
+
+impl Monkey {
+    fn as_op(&self) -> Option<Op> {
+        match self {
+            Monkey::Number(_) => None,
+            Monkey::Add(_, _) => Some(Op::Add),
+            Monkey::Sub(_, _) => Some(Op::Sub),
+            Monkey::Mul(_, _) => Some(Op::Mul),
+            Monkey::Div(_, _) => Some(Op::Div),
         }
     }
 }
 
-#[aoc(day21, part2)]
-pub fn get_human(input: &Monkeys) -> i64 {
-    let root = &input["root"];
-    let (a, b) = root.branches();
-    let mut value;
-    let mut branch;
-    if has_monkey(input, a, "humn") {
-        value = get_monkey(input, b);
-        branch = a;
-    } else {
-        value = get_monkey(input, a);
-        branch = b;
+impl Expr {
+    /// Builds the formula of `key`, replacing the monkey named `unknown`
+    /// (if any) by a variable. Formulas are handled recursively, so jobs
+    /// nested tens of thousands of levels deep can overflow the stack, unlike
+    /// `evaluate` and `solve`.
+    pub fn from_monkeys(map: &Monkeys, key: &str, unknown: Option<&str>) -> Result<Expr, JobError> {
+        topological_order(map, key)?;
+        Ok(Expr::build(map, key, unknown))
     }
 
-    while branch != "humn" {
-        let monkey = &input[branch];
+    fn build(map: &Monkeys, key: &str, unknown: Option<&str>) -> Expr {
+        if Some(key) == unknown {
+            return Expr::Unknown(key.to_string());
+        }
+        let monkey = &map[key];
         match monkey {
-            Monkey::Number(_) => panic!("Shouldn't happen"),
-            Monkey::Add(k1, k2)
-            | Monkey::Sub(k1, k2)
-            | Monkey::Mul(k1, k2)
-            | Monkey::Div(k1, k2) => {
-                let branch_value;
-                if has_monkey(input, k1, "humn") {
-                    branch = k1;
-                    branch_value = get_monkey(input, k2);
-                } else {
-                    branch = k2;
-                    branch_value = get_monkey(input, k1);
-                };
-                match monkey {
-                    Monkey::Add(_, _) => value -= branch_value,
-                    Monkey::Sub(left, _) => {
-                        if has_monkey(input, left, "humn") {
-                            value += branch_value
-                        } else {
-                            value = branch_value - value;
-                        }
+            Monkey::Number(n) => Expr::Number(Rational::from_integer(*n as i128)),
+            _ => {
+                let (k1, k2) = monkey.branches();
+                Expr::Op(
+                    monkey.as_op().unwrap(),
+                    Box::new(Expr::build(map, k1, unknown)),
+                    Box::new(Expr::build(map, k2, unknown)),
+                )
+            }
+        }
+    }
+
+    /// Replaces every subtree that doesn't depend on the unknown by its
+    /// value. Divisions by zero are kept as they are.
+    pub fn fold(&self) -> Expr {
+        match self {
+            Expr::Number(_) | Expr::Unknown(_) => self.clone(),
+            Expr::Op(op, a, b) => {
+                let (a, b) = (a.fold(), b.fold());
+                if let (Expr::Number(x), Expr::Number(y)) = (&a, &b) {
+                    match op {
+                        Op::Add => return Expr::Number(x + y),
+                        Op::Sub => return Expr::Number(x - y),
+                        Op::Mul => return Expr::Number(x * y),
+                        Op::Div if *y.numer() != 0 => return Expr::Number(x / y),
+                        Op::Div => (),
                     }
-                    Monkey::Mul(_, _) => value /= branch_value,
-                    Monkey::Div(left, _) => {
-                        if has_monkey(input, left, "humn") {
-                            value *= branch_value
-                        } else {
-                            value = branch_value / value;
-                        }
+                }
+                Expr::Op(*op, Box::new(a), Box::new(b))
+            }
+        }
+    }
+
+    /// Graphviz description of the formula, one node per number, unknown
+    /// and operation.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec!["digraph {".to_string()];
+        let mut open_set = vec![(self, 0)];
+        let mut next_id = 1;
+        while let Some((expr, id)) = open_set.pop() {
+            let label = match expr {
+                Expr::Number(n) => n.to_string(),
+                Expr::Unknown(name) => name.clone(),
+                Expr::Op(op, a, b) => {
+                    for child in [a, b] {
+                        lines.push(format!("  n{} -> n{};", id, next_id));
+                        open_set.push((child, next_id));
+                        next_id += 1;
                     }
-                    _ => panic!("Shouldn't happen"),
+                    op.to_string()
+                }
+            };
+            lines.push(format!("  n{} [label=\"{}\"];", id, label));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Op(Op::Add | Op::Sub, _, _) => 1,
+            Expr::Op(Op::Mul | Op::Div, _, _) => 2,
+            Expr::Number(n) if !n.is_integer() => 2,
+            _ => 3,
+        }
+    }
+
+    pub fn has_unknown(&self) -> bool {
+        match self {
+            Expr::Number(_) => false,
+            Expr::Unknown(_) => true,
+            Expr::Op(_, a, b) => a.has_unknown() || b.has_unknown(),
+        }
+    }
+
+    /// Reduces the formula to a linear function of the unknown.
+    pub fn linear(&self) -> Result<Linear, SolveError> {
+        match self {
+            Expr::Number(n) => Ok(Linear::constant(*n)),
+            Expr::Unknown(_) => Ok(Linear::unknown()),
+            Expr::Op(op, a, b) => Linear::combine(*op, &a.linear()?, &b.linear()?),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Infix notation, only adding the parentheses needed to keep the order
+    /// of the operations.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Unknown(name) => write!(f, "{}", name),
+            Expr::Op(op, a, b) => {
+                let precedence = self.precedence();
+                if a.precedence() < precedence {
+                    write!(f, "({})", a)?;
+                } else {
+                    write!(f, "{}", a)?;
+                }
+                write!(f, " {} ", op)?;
+                // a - (b + c) and a / (b * c) need them on the right side
+                let strict = matches!(op, Op::Sub | Op::Div);
+                if b.precedence() < precedence || (strict && b.precedence() == precedence) {
+                    write!(f, "({})", b)
+                } else {
+                    write!(f, "{}", b)
                 }
             }
         }
     }
-    value
+}
+
+/// Finds the values of the monkey `unknown` for which the two monkeys that
+/// `root` listens to satisfy `comparison` (left side against right side).
+/// Returns the bound `v` such that `unknown` compared against `v` holds.
+pub fn solve(
+    input: &Monkeys,
+    root: &str,
+    unknown: &str,
+    comparison: Comparison,
+) -> Result<(Comparison, Rational), SolveError> {
+    let monkey = match input.get(root) {
+        Some(monkey) => monkey,
+        None => {
+            return Err(SolveError::InvalidJobs(JobError::Undefined {
+                name: root.to_string(),
+                referenced_by: None,
+            }))
+        }
+    };
+    if monkey.as_op().is_none() {
+        return Err(SolveError::NotAnOperation(root.to_string()));
+    }
+    let (a, b) = monkey.branches();
+    let left = linear_form(input, a, unknown).map_err(SolveError::InvalidJobs)?;
+    let right = linear_form(input, b, unknown).map_err(SolveError::InvalidJobs)?;
+
+    let (side, other, comparison) = match (left.1, right.1) {
+        (true, true) => return Err(SolveError::UnknownOnBothSides),
+        (false, false) => return Err(SolveError::UnknownMissing),
+        (true, false) => (left.0, right.0, comparison),
+        (false, true) => (right.0, left.0, comparison.flip()),
+    };
+    let side = side?;
+    let value = other?.constant;
+    if side.is_constant() {
+        return Err(SolveError::NoUniqueSolution);
+    }
+
+    let bound = (value - side.constant) / side.coef;
+    let comparison = if *side.coef.numer() < 0 {
+        comparison.flip()
+    } else {
+        comparison
+    };
+    Ok((comparison, bound))
+}
+
+#[aoc(day21, part2)]
+pub fn get_human(input: &Monkeys) -> i64 {
+    let (_, value) = solve(input, "root", "humn", Comparison::Equal).unwrap();
+    assert!(value.is_integer(), "humn must yell {}", value);
+    value.to_integer() as i64
 }
 
 #[cfg(test)]
@@ -164,4 +535,225 @@ hmdt: 32";
         let input = parse_input(DAY21_EXAMPLE);
         assert_eq!(get_human(&input), 301);
     }
+
+    #[test]
+    fn test_day21_solve_comparisons() {
+        let input = parse_input(DAY21_EXAMPLE);
+        let value = Rational::from_integer(301);
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Less),
+            Ok((Comparison::Less, value))
+        );
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Greater),
+            Ok((Comparison::Greater, value))
+        );
+        // solve for another monkey, keeping humn at 5
+        assert_eq!(
+            solve(&input, "root", "dbpl", Comparison::Equal),
+            Ok((Comparison::Equal, Rational::new(1, 15)))
+        );
+        assert_eq!(
+            solve(&input, "root", "hmdt", Comparison::Equal),
+            Ok((Comparison::Equal, Rational::new(12, 5)))
+        );
+    }
+
+    #[test]
+    fn test_day21_solve_exact() {
+        // humn / 2 == 3 / 4 only has a fractional solution
+        let input = parse_input(
+            "root: left + right
+left: humn / two
+two: 2
+right: three / four
+three: 3
+four: 4
+humn: 1",
+        );
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Equal),
+            Ok((Comparison::Equal, Rational::new(3, 2)))
+        );
+        let input = parse_input(
+            "root: left + two
+left: two / humn
+two: 2
+humn: 1",
+        );
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Equal),
+            Err(SolveError::NonLinear)
+        );
+        let input = parse_input(
+            "root: left + right
+left: humn * two
+right: humn - two
+two: 2
+humn: 1",
+        );
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Equal),
+            Err(SolveError::UnknownOnBothSides)
+        );
+        assert_eq!(
+            solve(&input, "root", "two", Comparison::Equal),
+            Err(SolveError::UnknownOnBothSides)
+        );
+        assert_eq!(
+            solve(&input, "root", "nobody", Comparison::Equal),
+            Err(SolveError::UnknownMissing)
+        );
+    }
+
+    #[test]
+    fn test_day21_solve_negative_coef() {
+        // 10 - humn < 4 means humn > 6
+        let input = parse_input(
+            "root: left + four
+left: ten - humn
+ten: 10
+four: 4
+humn: 1",
+        );
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Less),
+            Ok((Comparison::Greater, Rational::from_integer(6)))
+        );
+    }
+
+    #[test]
+    fn test_day21_print_formula() {
+        let input = parse_input(DAY21_EXAMPLE);
+        let expr = Expr::from_monkeys(&input, "root", Some("humn")).unwrap();
+        assert_eq!(expr.to_string(), "(4 + 2 * (humn - 3)) / 4 + (32 - 2) * 5");
+        assert_eq!(expr.fold().to_string(), "(4 + 2 * (humn - 3)) / 4 + 150");
+        assert_eq!(
+            Expr::from_monkeys(&input, "drzm", None).unwrap().fold(),
+            Expr::Number(Rational::from_integer(30))
+        );
+        let nested = Expr::Op(
+            Op::Sub,
+            Box::new(Expr::Number(Rational::new(1, 2))),
+            Box::new(Expr::Op(
+                Op::Sub,
+                Box::new(Expr::Unknown("x".to_string())),
+                Box::new(Expr::Number(Rational::from_integer(1))),
+            )),
+        );
+        assert_eq!(nested.to_string(), "1/2 - (x - 1)");
+    }
+
+    #[test]
+    fn test_day21_dot() {
+        let input = parse_input(DAY21_EXAMPLE);
+        let dot = Expr::from_monkeys(&input, "pppw", Some("humn"))
+            .unwrap()
+            .fold()
+            .to_dot();
+        assert!(dot.starts_with("digraph {\n"));
+        assert!(dot.ends_with("\n}"));
+        assert_eq!(dot.matches(" -> ").count(), 8);
+        assert_eq!(dot.matches("[label=").count(), 9);
+        assert!(dot.contains("[label=\"humn\"]"));
+    }
+
+    #[test]
+    fn test_day21_topological_order() {
+        let input = parse_input(DAY21_EXAMPLE);
+        let order = topological_order(&input, "root").unwrap();
+        assert_eq!(order.len(), input.len());
+        assert_eq!(order.last().unwrap(), "root");
+        let position = |key: &str| order.iter().position(|k| k == key).unwrap();
+        assert!(position("humn") < position("ptdq"));
+        assert!(position("ptdq") < position("lgvd"));
+        assert_eq!(topological_order(&input, "dbpl").unwrap(), vec!["dbpl"]);
+    }
+
+    #[test]
+    fn test_day21_invalid_jobs() {
+        let input = parse_input(
+            "root: aaaa + bbbb
+aaaa: cccc * dddd
+bbbb: 3
+cccc: 2",
+        );
+        let undefined = JobError::Undefined {
+            name: "dddd".to_string(),
+            referenced_by: Some("aaaa".to_string()),
+        };
+        assert_eq!(topological_order(&input, "root"), Err(undefined.clone()));
+        assert_eq!(evaluate(&input, "root"), Err(undefined));
+        assert_eq!(
+            evaluate(&input, "nope"),
+            Err(JobError::Undefined {
+                name: "nope".to_string(),
+                referenced_by: None,
+            })
+        );
+
+        let input = parse_input(
+            "root: aaaa + bbbb
+aaaa: cccc * bbbb
+bbbb: 3
+cccc: dddd - bbbb
+dddd: aaaa / bbbb",
+        );
+        assert_eq!(
+            evaluate(&input, "root"),
+            Err(JobError::Cycle(
+                ["aaaa", "cccc", "dddd", "aaaa"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            ))
+        );
+        assert!(matches!(
+            solve(&input, "root", "humn", Comparison::Equal),
+            Err(SolveError::InvalidJobs(JobError::Cycle(_)))
+        ));
+
+        let input = parse_input(
+            "root: aaaa / bbbb
+aaaa: 3
+bbbb: 0",
+        );
+        assert_eq!(
+            evaluate(&input, "root"),
+            Err(JobError::DivisionByZero("root".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_day21_deep_tree() {
+        // a chain deep enough to overflow the stack when recursing
+        let depth = 100_000;
+        let mut input = Monkeys::new();
+        for i in 0..depth {
+            let job = Monkey::Add(format!("m{}", i + 1), "one".to_string());
+            input.insert(format!("m{}", i), job);
+        }
+        input.insert(format!("m{}", depth), Monkey::Number(0));
+        input.insert("one".to_string(), Monkey::Number(1));
+        assert_eq!(evaluate(&input, "m0"), Ok(depth as i64));
+
+        // m0 == humn + depth
+        input.insert(
+            format!("m{}", depth),
+            Monkey::Add("humn".to_string(), "one".to_string()),
+        );
+        input.insert("humn".to_string(), Monkey::Number(0));
+        input.insert("target".to_string(), Monkey::Number(1_000_000));
+        input.insert(
+            "root".to_string(),
+            Monkey::Add("m0".to_string(), "target".to_string()),
+        );
+        assert_eq!(
+            solve(&input, "root", "humn", Comparison::Equal),
+            Ok((
+                Comparison::Equal,
+                Rational::from_integer(1_000_000 - depth - 1)
+            ))
+        );
+    }
 }
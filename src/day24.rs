@@ -4,6 +4,11 @@ use aoc_runner_derive::aoc_generator;
 use priority_queue::DoublePriorityQueue;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
 
 #[derive(Clone)]
 pub struct Blizzard {
@@ -37,11 +42,9 @@ pub struct Valley {
 }
 
 impl Valley {
-    #[allow(dead_code)]
-    fn print(&self, minutes: usize, pos: Option<(i32, i32)>) -> String {
+    fn print(&self, state: &[Blizzard], pos: Option<(i32, i32)>) -> String {
         let mut ret = "".to_string();
         let (width, height) = self.map.size();
-        let state = self.tick(minutes);
         for y in 0..height as i32 {
             for x in 0..width as i32 {
                 if let Some(current) = pos {
@@ -59,7 +62,7 @@ impl Valley {
                         ret.push(blizzards.len().to_string().chars().next().unwrap());
                         continue;
                     }
-                    ret.push(blizzards.get(0).unwrap().dir);
+                    ret.push(blizzards.first().unwrap().dir);
                     continue;
                 }
                 let c = self.map.cell_at(x, y).unwrap();
@@ -70,6 +73,36 @@ impl Valley {
         ret
     }
 
+    fn pixels(&self, state: &[Blizzard], pos: (i32, i32), scale: usize) -> Vec<u8> {
+        let (width, height) = self.map.size();
+        // colour every cell once, blizzards and then the expedition on top
+        let mut colors = (0..width * height)
+            .map(
+                |i| match self.map.cell_at((i % width) as i32, (i / width) as i32) {
+                    Some('#') => GIF_WALL,
+                    _ => GIF_GROUND,
+                },
+            )
+            .collect::<Vec<u8>>();
+        let index = |(x, y): (i32, i32)| y as usize * width + x as usize;
+        for b in state.iter() {
+            colors[index(b.pos)] = GIF_BLIZZARD;
+        }
+        colors[index(pos)] = GIF_EXPEDITION;
+
+        let mut ret = Vec::with_capacity(width * height * scale * scale);
+        for row in colors.chunks(width) {
+            let line = row
+                .iter()
+                .flat_map(|c| std::iter::repeat_n(*c, scale))
+                .collect::<Vec<u8>>();
+            for _ in 0..scale {
+                ret.extend_from_slice(&line);
+            }
+        }
+        ret
+    }
+
     fn simulate(&self, blizzards: &mut [Blizzard], minutes: usize) {
         let (width, height) = self.map.size();
         for b in blizzards.iter_mut() {
@@ -107,7 +140,7 @@ pub fn parse_input(input: &str) -> Valley {
         .map(|s| s.chars().collect::<Vec<char>>())
         .collect::<Vec<Vec<char>>>();
     let height = rows.len() as i32;
-    let width = rows.get(0).unwrap().len() as i32;
+    let width = rows.first().unwrap().len() as i32;
     let mut cells = rows.into_iter().flatten().collect::<Vec<char>>();
     let mut blizzards = Vec::new();
     for y in 0..height {
@@ -137,12 +170,34 @@ fn dist(pos: (i32, i32), goal: (i32, i32)) -> u32 {
     (goal.0 - pos.0).unsigned_abs() + (goal.1 - pos.1).unsigned_abs()
 }
 
-pub fn find_path(input: &Valley) -> Option<i32> {
+pub struct Expedition {
+    pub start_minute: usize,
+    pub minutes: usize,
+    pub legs: Vec<usize>,
+    pub route: Option<Vec<(i32, i32)>>,
+}
+
+// position and minute of the expedition
+type State = ((i32, i32), usize);
+
+fn is_blocked(state: &[Blizzard], pos: (i32, i32)) -> bool {
+    state.iter().any(|b| b.pos == pos)
+}
+
+fn find_leg(
+    input: &Valley,
+    from: (i32, i32),
+    to: (i32, i32),
+    start_minute: usize,
+) -> Option<Vec<(i32, i32)>> {
     let mut open_set = DoublePriorityQueue::new();
-    open_set.push((input.start, 0), dist(input.start, input.end) as usize);
+    open_set.push((from, start_minute), start_minute + dist(from, to) as usize);
 
     let mut state_cache: BTreeMap<usize, Vec<Blizzard>> = BTreeMap::new();
     let mut closed_set = BTreeSet::new();
+    // every state at minute t+1 is reached from one at minute t, so the
+    // first predecessor we see is as good as any other
+    let mut came_from: BTreeMap<State, State> = BTreeMap::new();
 
     while !open_set.is_empty() {
         let (elem, priority) = open_set.pop_min().unwrap();
@@ -152,8 +207,15 @@ pub fn find_path(input: &Valley) -> Option<i32> {
         }
         closed_set.insert(elem);
 
-        if current == input.end {
-            return Some(time as i32);
+        if current == to {
+            let mut route = vec![current];
+            let mut step = elem;
+            while let Some(prev) = came_from.get(&step) {
+                route.push(prev.0);
+                step = *prev;
+            }
+            route.reverse();
+            return Some(route);
         }
 
         let state = match state_cache.get(&(time + 1)) {
@@ -172,9 +234,10 @@ pub fn find_path(input: &Valley) -> Option<i32> {
                 state_cache.get(&(time + 1)).unwrap()
             }
         };
-        if !state.iter().any(|b| b.pos == current) {
+        if !is_blocked(state, current) {
             // wait state
             open_set.push((current, time + 1), priority + 1);
+            came_from.entry((current, time + 1)).or_insert(elem);
         }
         for (c, cell_pos) in input.map.neighbors_at(current.0, current.1) {
             let candidate = (cell_pos.0 as i32, cell_pos.1 as i32);
@@ -185,18 +248,146 @@ pub fn find_path(input: &Valley) -> Option<i32> {
                 continue;
             }
 
-            if state.iter().any(|b| b.pos == candidate) {
+            if is_blocked(state, candidate) {
                 continue;
             }
 
-            let dist = dist(candidate, input.end) as usize;
-            let score = dist + time;
+            let score = time + 1 + dist(candidate, to) as usize;
             open_set.push((candidate, time + 1), score);
+            came_from.entry((candidate, time + 1)).or_insert(elem);
         }
     }
     None
 }
 
+/// Walks through the valley visiting `waypoints` in order, leaving the first
+/// one at `start_minute`. Returns `None` if a waypoint isn't an open cell
+/// (or is under a blizzard when departing) or if some leg can't be completed.
+pub fn plan_expedition(
+    input: &Valley,
+    waypoints: &[(i32, i32)],
+    start_minute: usize,
+    with_route: bool,
+) -> Option<Expedition> {
+    let first = *waypoints.first()?;
+    if waypoints
+        .iter()
+        .any(|w| input.map.cell_at(w.0, w.1) != Some('.'))
+    {
+        return None;
+    }
+    if is_blocked(&input.tick(start_minute), first) {
+        return None;
+    }
+
+    let mut legs = Vec::new();
+    let mut route = vec![first];
+    let mut minute = start_minute;
+    for leg in waypoints.windows(2) {
+        let path = find_leg(input, leg[0], leg[1], minute)?;
+        let minutes = path.len() - 1;
+        legs.push(minutes);
+        minute += minutes;
+        if with_route {
+            route.extend(path.into_iter().skip(1));
+        }
+    }
+
+    Some(Expedition {
+        start_minute,
+        minutes: minute - start_minute,
+        legs,
+        route: if with_route { Some(route) } else { None },
+    })
+}
+
+// palette indices used for the GIF frames
+const GIF_GROUND: u8 = 0;
+const GIF_WALL: u8 = 1;
+const GIF_BLIZZARD: u8 = 2;
+const GIF_EXPEDITION: u8 = 3;
+const GIF_PALETTE: [u8; 12] = [
+    0xf0, 0xf0, 0xf0, // ground
+    0x40, 0x40, 0x40, // wall
+    0x80, 0xc0, 0xff, // blizzard
+    0xe0, 0x20, 0x20, // expedition
+];
+
+/// Renders the valley at every minute of the expedition's route, as `print`
+/// does for a single minute. Returns `None` if the route wasn't recorded.
+pub fn render_expedition(input: &Valley, expedition: &Expedition) -> Option<Vec<String>> {
+    let route = expedition.route.as_ref()?;
+    let mut state = input.tick(expedition.start_minute);
+    let mut frames = Vec::new();
+    for (i, pos) in route.iter().enumerate() {
+        if i > 0 {
+            input.simulate(&mut state, 1);
+        }
+        frames.push(format!(
+            "Minute {}:\n{}",
+            expedition.start_minute + i,
+            input.print(&state, Some(*pos))
+        ));
+    }
+    Some(frames)
+}
+
+/// Encodes the expedition's route as an animated GIF, drawing every cell as
+/// a `scale`x`scale` square and showing each minute for `delay` hundredths
+/// of a second. Fails if `scale` is zero or makes the image wider or taller
+/// than a GIF allows.
+pub fn write_expedition_gif<W: Write>(
+    input: &Valley,
+    expedition: &Expedition,
+    writer: W,
+    scale: usize,
+    delay: u16,
+) -> Result<(), gif::EncodingError> {
+    let route = match &expedition.route {
+        Some(route) => route,
+        None => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "route not recorded").into())
+        }
+    };
+    let scaled = |size: usize| {
+        size.checked_mul(scale)
+            .filter(|&size| size > 0)
+            .and_then(|size| u16::try_from(size).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid scale"))
+    };
+    let (width, height) = input.map.size();
+    let (width, height) = (scaled(width)?, scaled(height)?);
+
+    let mut encoder = gif::Encoder::new(writer, width, height, &GIF_PALETTE)?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    let mut state = input.tick(expedition.start_minute);
+    for (i, pos) in route.iter().enumerate() {
+        if i > 0 {
+            input.simulate(&mut state, 1);
+        }
+        let pixels = input.pixels(&state, *pos, scale);
+        let mut frame = gif::Frame::from_indexed_pixels(width, height, &pixels, None);
+        frame.delay = delay;
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+pub fn save_expedition_gif(
+    input: &Valley,
+    expedition: &Expedition,
+    path: &Path,
+    scale: usize,
+    delay: u16,
+) -> Result<(), gif::EncodingError> {
+    let file = File::create(path)?;
+    write_expedition_gif(input, expedition, BufWriter::new(file), scale, delay)
+}
+
+pub fn find_path(input: &Valley) -> Option<i32> {
+    find_leg(input, input.start, input.end, 0).map(|path| (path.len() - 1) as i32)
+}
+
 #[aoc(day24, part1)]
 pub fn shortest_path_minutes(input: &Valley) -> u64 {
     find_path(input).unwrap() as u64
@@ -204,17 +395,10 @@ pub fn shortest_path_minutes(input: &Valley) -> u64 {
 
 #[aoc(day24, part2)]
 pub fn shortest_path_part2(input: &Valley) -> u64 {
-    let mut valley = input.clone();
-    let step1 = find_path(&valley).unwrap();
-    valley.blizzards = valley.tick(step1 as usize);
-    valley.start = input.end;
-    valley.end = input.start;
-    let step2 = find_path(&valley).unwrap();
-    valley.blizzards = valley.tick(step2 as usize);
-    valley.start = input.start;
-    valley.end = input.end;
-    let step3 = find_path(&valley).unwrap();
-    (step1 + step2 + step3) as u64
+    let waypoints = [input.start, input.end, input.start, input.end];
+    plan_expedition(input, &waypoints, 0, false)
+        .unwrap()
+        .minutes as u64
 }
 
 #[cfg(test)]
@@ -239,4 +423,97 @@ mod tests {
         let input = parse_input(DAY24_EXAMPLE);
         assert_eq!(shortest_path_part2(&input), 54);
     }
+
+    #[test]
+    fn test_day24_expedition_legs() {
+        let input = parse_input(DAY24_EXAMPLE);
+        let waypoints = [input.start, input.end, input.start, input.end];
+        let expedition = plan_expedition(&input, &waypoints, 0, false).unwrap();
+        assert_eq!(expedition.legs, vec![18, 23, 13]);
+        assert_eq!(expedition.minutes, 54);
+        assert!(expedition.route.is_none());
+    }
+
+    #[test]
+    fn test_day24_expedition_route() {
+        let input = parse_input(DAY24_EXAMPLE);
+        let waypoints = [input.start, (3, 2), input.end];
+        let expedition = plan_expedition(&input, &waypoints, 0, true).unwrap();
+        let route = expedition.route.unwrap();
+        assert_eq!(route.len(), expedition.minutes + 1);
+        assert_eq!(route.first(), Some(&input.start));
+        assert_eq!(route.last(), Some(&input.end));
+        assert_eq!(route[expedition.legs[0]], (3, 2));
+        for step in route.windows(2) {
+            assert!(dist(step[0], step[1]) <= 1);
+        }
+    }
+
+    #[test]
+    fn test_day24_render_expedition() {
+        let input = parse_input(DAY24_EXAMPLE);
+        let expedition = plan_expedition(&input, &[input.start, input.end], 0, true).unwrap();
+        let frames = render_expedition(&input, &expedition).unwrap();
+        assert_eq!(frames.len(), 19);
+        assert_eq!(
+            frames[0],
+            "Minute 0:
+#E######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#
+"
+        );
+        assert_eq!(
+            frames[18],
+            "Minute 18:
+#.######
+#>2.<.<#
+#.2v^2<#
+#>..>2>#
+#<....>#
+######E#
+"
+        );
+    }
+
+    #[test]
+    fn test_day24_expedition_gif() {
+        let input = parse_input(DAY24_EXAMPLE);
+        let expedition = plan_expedition(&input, &[input.start, input.end], 0, true).unwrap();
+        let mut buffer = Vec::new();
+        write_expedition_gif(&input, &expedition, &mut buffer, 4, 20).unwrap();
+        assert!(buffer.starts_with(b"GIF89a"));
+
+        let without_route = plan_expedition(&input, &[input.start, input.end], 0, false).unwrap();
+        assert!(write_expedition_gif(&input, &without_route, Vec::new(), 4, 20).is_err());
+
+        assert!(write_expedition_gif(&input, &expedition, Vec::new(), 0, 20).is_err());
+        assert!(write_expedition_gif(&input, &expedition, Vec::new(), 10_000, 20).is_err());
+        assert!(write_expedition_gif(&input, &expedition, Vec::new(), usize::MAX, 20).is_err());
+    }
+
+    #[test]
+    fn test_day24_pixels() {
+        let input = parse_input(DAY24_EXAMPLE);
+        let state = input.tick(3);
+        let (width, _) = input.map.size();
+        let pixels = input.pixels(&state, (1, 1), 3);
+        for (y, line) in input.print(&state, Some((1, 1))).lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let expected = match c {
+                    'E' => GIF_EXPEDITION,
+                    '#' => GIF_WALL,
+                    '.' => GIF_GROUND,
+                    _ => GIF_BLIZZARD,
+                };
+                for (dx, dy) in itertools::iproduct!(0..3, 0..3) {
+                    let i = (y * 3 + dy) * width * 3 + x * 3 + dx;
+                    assert_eq!(pixels[i], expected);
+                }
+            }
+        }
+    }
 }